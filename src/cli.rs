@@ -1,12 +1,16 @@
-use clap::Parser;
+use crate::expr::Token;
+use crate::error::{PergError, Result};
+use clap::{ArgAction, ArgGroup, ArgMatches, Parser};
 
 /// perg - A fast text search tool similar to grep
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(group(ArgGroup::new("expression_patterns").args(["regexp", "pattern_files"]).multiple(true)))]
+#[command(group(ArgGroup::new("aggregate").args(["frequency", "unique"])))]
 pub struct Args {
     /// Pattern to search for (supports regular expressions)
-    #[arg(value_name = "PATTERN")]
-    pub pattern: String,
+    #[arg(value_name = "PATTERN", required_unless_present_any = ["number_range", "cidr", "expression_patterns"])]
+    pub pattern: Option<String>,
 
     /// Files or directories to search in
     #[arg(value_name = "PATH")]
@@ -75,4 +79,309 @@ pub struct Args {
     /// Print colorized output
     #[arg(long = "color", default_value = "auto")]
     pub color: String,
+
+    /// Set an output color, e.g. 'path:fg:magenta', 'match:bg:0x20,0x20,0x40' or 'line:none' (repeatable)
+    #[arg(long = "colors", value_name = "SPEC", action = ArgAction::Append)]
+    pub colors: Vec<String>,
+
+    /// Link paths and line numbers in colored output to a URL: a template with {host}, {path}, {line} and {column}, or one of default, file, kitty, vscode, vscode-insiders, cursor, macvim and textmate
+    #[arg(long = "hyperlink-format", value_name = "FORMAT")]
+    pub hyperlink_format: Option<String>,
+
+    /// Print every line, highlighting matches and printing the other lines as context
+    #[arg(long = "passthru", conflicts_with_all = ["only_matching", "count", "files_with_matches", "files_without_match", "extract", "aggregate"])]
+    pub passthru: bool,
+
+    /// Replace lines longer than N bytes with a notice of how many matches they have
+    #[arg(long = "max-columns", value_name = "N")]
+    pub max_columns: Option<usize>,
+
+    /// Instead of omitting long lines, show N bytes around the first match
+    #[arg(long = "max-columns-preview", requires = "max_columns")]
+    pub max_columns_preview: bool,
+
+    /// Print SEP between groups of context lines that aren't adjacent [default: --]
+    #[arg(long = "group-separator", value_name = "SEP", allow_hyphen_values = true)]
+    pub group_separator: Option<String>,
+
+    /// Print nothing between groups of context lines
+    #[arg(long = "no-group-separator", conflicts_with = "group_separator")]
+    pub no_group_separator: bool,
+
+    /// Separator after the path, line and column of matching lines [default: :]
+    #[arg(long = "field-match-separator", value_name = "SEP", allow_hyphen_values = true)]
+    pub field_match_separator: Option<String>,
+
+    /// Separator after the path and line number of context lines [default: -]
+    #[arg(long = "field-context-separator", value_name = "SEP", allow_hyphen_values = true)]
+    pub field_context_separator: Option<String>,
+
+    /// Print each file's path once above its lines, with line numbers (default on a terminal)
+    #[arg(long = "heading", overrides_with = "no_heading", conflicts_with_all = ["format", "json", "extract", "aggregate"])]
+    pub heading: bool,
+
+    /// Prefix every line with its path instead of printing headings
+    #[arg(long = "no-heading", overrides_with = "heading")]
+    pub no_heading: bool,
+
+    /// Enable matching across line boundaries
+    #[arg(short = 'U', long = "multiline", conflicts_with_all = ["record_separator", "paragraph", "record_start", "since", "until", "csv_column", "json_path", "key_path", "number_range", "cidr", "fuzzy", "normalize", "ignore_diacritics", "expression_patterns"])]
+    pub multiline: bool,
+
+    /// Make `.` match newlines in multiline mode
+    #[arg(long = "multiline-dotall", requires = "multiline")]
+    pub multiline_dotall: bool,
+
+    /// Treat input and output data as NUL-terminated records instead of lines
+    #[arg(short = 'z', long = "null-data")]
+    pub null_data: bool,
+
+    /// Print a NUL byte after each filename
+    #[arg(short = 'Z', long = "null")]
+    pub null: bool,
+
+    /// Split input into records at each match of REGEX instead of at newlines
+    #[arg(long = "record-separator", value_name = "REGEX")]
+    pub record_separator: Option<String>,
+
+    /// Treat blank-line separated paragraphs as records
+    #[arg(long = "paragraph", conflicts_with = "record_separator")]
+    pub paragraph: bool,
+
+    /// Start a new record at each line matching REGEX, folding other lines into the previous record
+    #[arg(long = "record-start", value_name = "REGEX", conflicts_with_all = ["record_separator", "paragraph", "null_data"])]
+    pub record_start: Option<String>,
+
+    /// Only search lines timestamped at or after TIME (e.g. 2026-10-01T10:00)
+    #[arg(long = "since", value_name = "TIME")]
+    pub since: Option<String>,
+
+    /// Only search lines timestamped at or before TIME
+    #[arg(long = "until", value_name = "TIME")]
+    pub until: Option<String>,
+
+    /// Parse line timestamps with a strftime-style FORMAT instead of detecting them
+    #[arg(long = "time-format", value_name = "FORMAT")]
+    pub time_format: Option<String>,
+
+    /// Read input as CSV and match the pattern against COLUMN (header name or 1-based number)
+    #[arg(long = "csv-column", value_name = "COLUMN", conflicts_with_all = ["record_separator", "paragraph", "record_start", "null_data"])]
+    pub csv_column: Option<String>,
+
+    /// Read input as tab-separated values instead of comma-separated
+    #[arg(long = "tsv", requires = "csv_column")]
+    pub tsv: bool,
+
+    /// Field delimiter for CSV input
+    #[arg(long = "csv-delimiter", value_name = "CHAR", requires = "csv_column", conflicts_with = "tsv")]
+    pub csv_delimiter: Option<char>,
+
+    /// Treat the first CSV record as data rather than a header row
+    #[arg(long = "csv-no-header", requires = "csv_column")]
+    pub csv_no_header: bool,
+
+    /// Print only these comma-separated columns of matching records
+    #[arg(long = "csv-output", value_name = "COLUMNS", requires = "csv_column", value_delimiter = ',')]
+    pub csv_output: Vec<String>,
+
+    /// Parse input as JSON (Lines) and match the pattern against values at PATH, e.g. `.items[*].name`
+    #[arg(long = "json-path", value_name = "PATH", conflicts_with_all = ["record_separator", "paragraph", "record_start", "null_data", "csv_column"])]
+    pub json_path: Option<String>,
+
+    /// Pretty-print matching JSON documents
+    #[arg(long = "json-pretty", requires = "json_path")]
+    pub json_pretty: bool,
+
+    /// Parse input as YAML or TOML and match the pattern against values at PATH, e.g. `spec.containers[*].image`
+    #[arg(long = "key-path", value_name = "PATH", conflicts_with_all = ["record_separator", "paragraph", "record_start", "null_data", "csv_column", "json_path"])]
+    pub key_path: Option<String>,
+
+    /// Input format for --key-path instead of detecting it from the file extension
+    #[arg(long = "input-format", value_name = "FORMAT", value_parser = ["yaml", "toml"], requires = "key_path")]
+    pub input_format: Option<String>,
+
+    /// Match numbers within RANGE instead of a pattern, e.g. `>500`, `[100,200)` or `1.5..3`;
+    /// every positional argument is then a path
+    #[arg(long = "number-range", value_name = "RANGE", allow_hyphen_values = true)]
+    pub number_range: Option<String>,
+
+    /// Only consider numbers directly following KEY, e.g. `latency_ms=`
+    #[arg(long = "number-key", value_name = "KEY", requires = "number_range")]
+    pub number_key: Option<String>,
+
+    /// Match IPv4/IPv6 addresses inside any of the comma-separated NETWORKS instead of a pattern,
+    /// e.g. `10.0.0.0/8,2001:db8::/32`; every positional argument is then a path
+    #[arg(long = "cidr", value_name = "NETWORKS", value_delimiter = ',', conflicts_with = "number_range")]
+    pub cidr: Vec<String>,
+
+    /// Match PATTERN as a literal allowing up to K insertions, deletions or substitutions
+    #[arg(long = "fuzzy", value_name = "K", conflicts_with_all = ["number_range", "cidr", "expression_patterns"])]
+    pub fuzzy: Option<usize>,
+
+    /// Print each output line as a JSON object with its matches (and their edit distance with --fuzzy)
+    #[arg(long = "json")]
+    pub json: bool,
+
+    /// Normalize the pattern and input to FORM before matching
+    #[arg(long = "normalize", value_name = "FORM", value_parser = ["nfc", "nfkc"])]
+    pub normalize: Option<String>,
+
+    /// Match letters regardless of accents and other diacritics, e.g. `cafe` finds `café` and `Strasse` finds `Straße`
+    #[arg(long = "ignore-diacritics")]
+    pub ignore_diacritics: bool,
+
+    /// Show the column number of the first match
+    #[arg(long = "column")]
+    pub column: bool,
+
+    /// Use PATTERN for matching; repeat for several patterns (any may match) or combine
+    /// them with --and, --or, --not and `(` `)` arguments; every positional argument is then a path
+    #[arg(short = 'e', long = "regexp", value_name = "PATTERN", action = ArgAction::Append, allow_hyphen_values = true, conflicts_with_all = ["number_range", "cidr"])]
+    pub regexp: Vec<String>,
+
+    /// Read patterns from FILE, one per line, any of which may match; a line `LABEL<TAB>PATTERN`
+    /// names the pattern for --pattern-id and --pattern-stats
+    #[arg(short = 'f', long = "file", value_name = "FILE", action = ArgAction::Append, conflicts_with_all = ["number_range", "cidr"])]
+    pub pattern_files: Vec<String>,
+
+    /// Match only when the expressions on both sides match
+    #[arg(long = "and", action = ArgAction::Count, requires = "expression_patterns")]
+    pub and: u8,
+
+    /// Match when either expression matches (the default between patterns)
+    #[arg(long = "or", action = ArgAction::Count, requires = "expression_patterns")]
+    pub or: u8,
+
+    /// Match when the following expression does not
+    #[arg(long = "not", action = ArgAction::Count, requires = "expression_patterns")]
+    pub not: u8,
+
+    /// Only report files in which every -e pattern matches somewhere, on any line
+    #[arg(long = "all-match", requires = "expression_patterns")]
+    pub all_match: bool,
+
+    /// Match lines of the first -e pattern within N lines of the second, showing the lines between
+    #[arg(long = "near", value_name = "N", requires = "expression_patterns", conflicts_with_all = ["and", "or", "not", "all_match", "invert_match"])]
+    pub near: Option<usize>,
+
+    /// Match lines of the first -e pattern with no line of the second within N lines
+    #[arg(long = "not-near", value_name = "N", requires = "expression_patterns", conflicts_with_all = ["near", "and", "or", "not", "all_match", "invert_match"])]
+    pub not_near: Option<usize>,
+
+    /// Prefix each output line with the labels (by default the 1-based numbers) of the patterns it matched
+    #[arg(long = "pattern-id", conflicts_with_all = ["number_range", "cidr", "fuzzy"])]
+    pub pattern_id: bool,
+
+    /// Print a table of matching line counts per pattern after the search
+    #[arg(long = "pattern-stats", conflicts_with_all = ["number_range", "cidr", "fuzzy"])]
+    pub pattern_stats: bool,
+
+    /// Instead of printing lines, print each distinct match with its count, most frequent first
+    #[arg(long = "frequency", conflicts_with_all = ["count", "files_with_matches", "files_without_match", "json"])]
+    pub frequency: bool,
+
+    /// Instead of printing lines, print each distinct match once, in order of first appearance
+    #[arg(long = "unique", conflicts_with_all = ["frequency", "count", "files_with_matches", "files_without_match", "json"])]
+    pub unique: bool,
+
+    /// Only print the first N entries of --frequency or --unique
+    #[arg(long = "top", value_name = "N", requires = "aggregate")]
+    pub top: Option<usize>,
+
+    /// Use the text of capture GROUP (number or name) instead of the whole match
+    #[arg(long = "group", value_name = "GROUP", requires = "aggregate", conflicts_with_all = ["number_range", "cidr", "fuzzy", "expression_patterns", "normalize", "ignore_diacritics"])]
+    pub group: Option<String>,
+
+    /// Break --frequency or --unique counts down per file
+    #[arg(long = "per-file", requires = "aggregate")]
+    pub per_file: bool,
+
+    /// Print the capture groups of each match as csv, tsv or json rows; -H and -n add path and line columns
+    #[arg(long = "extract", value_name = "FORMAT", value_parser = ["csv", "tsv", "json"], conflicts_with_all = ["number_range", "cidr", "fuzzy", "expression_patterns", "normalize", "ignore_diacritics", "multiline", "invert_match", "count", "files_with_matches", "files_without_match", "json", "aggregate"])]
+    pub extract: Option<String>,
+
+    /// Print each output line from TEMPLATE, e.g. '{path}:{line}:{col}: {match}'
+    #[arg(long = "format", value_name = "TEMPLATE", conflicts_with_all = ["count", "files_with_matches", "files_without_match", "json", "aggregate", "extract"])]
+    pub format: Option<String>,
+}
+
+impl Args {
+    /// Whether an option supplies the pattern, so the first positional argument is a path
+    pub fn pattern_from_option(&self) -> bool {
+        self.number_range.is_some() || !self.cidr.is_empty() || self.has_expression()
+    }
+
+    /// Whether patterns come from `-e` or `-f`, possibly combined into an expression
+    pub fn has_expression(&self) -> bool {
+        !self.regexp.is_empty() || !self.pattern_files.is_empty()
+    }
+
+    /// Treat every positional argument as a path when an option supplies the pattern
+    pub fn shift_positionals(&mut self) {
+        if self.pattern_from_option() {
+            if let Some(path) = self.pattern.take() {
+                self.paths.insert(0, path);
+            }
+        }
+        // Parentheses group `-e` expressions rather than name files
+        if self.has_expression() {
+            self.paths.retain(|path| path != "(" && path != ")");
+        }
+    }
+}
+
+/// Collect `-e` and `-f` patterns, boolean operators and parentheses in command-line order
+pub fn expression_tokens(matches: &ArgMatches) -> Result<Vec<Token>> {
+    let mut tokens: Vec<(usize, Token)> = Vec::new();
+    if let (Some(indices), Some(patterns)) = (matches.indices_of("regexp"), matches.get_many::<String>("regexp")) {
+        tokens.extend(indices.zip(patterns).map(|(idx, pattern)| {
+            let token = Token::Pattern {
+                pattern: pattern.clone(),
+                label: None,
+            };
+            (idx, token)
+        }));
+    }
+    if let (Some(indices), Some(files)) = (matches.indices_of("pattern_files"), matches.get_many::<String>("pattern_files")) {
+        for (idx, file) in indices.zip(files) {
+            tokens.extend(read_pattern_file(file)?.into_iter().map(|token| (idx, token)));
+        }
+    }
+    for (id, token) in [("and", Token::And), ("or", Token::Or), ("not", Token::Not)] {
+        if matches.get_count(id) > 0 {
+            tokens.extend(matches.indices_of(id).into_iter().flatten().map(|idx| (idx, token.clone())));
+        }
+    }
+    for id in ["pattern", "paths"] {
+        if let (Some(indices), Some(values)) = (matches.indices_of(id), matches.get_many::<String>(id)) {
+            tokens.extend(indices.zip(values).filter_map(|(idx, value)| match value.as_str() {
+                "(" => Some((idx, Token::Open)),
+                ")" => Some((idx, Token::Close)),
+                _ => None,
+            }));
+        }
+    }
+    // Stable, so the patterns of one file keep their order
+    tokens.sort_by_key(|&(idx, _)| idx);
+    Ok(tokens.into_iter().map(|(_, token)| token).collect())
+}
+
+/// Read the patterns of a `-f` file, skipping empty lines
+fn read_pattern_file(path: &str) -> Result<Vec<Token>> {
+    let content = std::fs::read_to_string(path).map_err(|err| PergError::InvalidPattern(format!("{}: {}", path, err)))?;
+    Ok(content
+        .lines()
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .filter(|line| !line.is_empty())
+        .map(|line| match line.split_once('\t') {
+            Some((label, pattern)) => Token::Pattern {
+                pattern: pattern.to_string(),
+                label: Some(label.to_string()),
+            },
+            None => Token::Pattern {
+                pattern: line.to_string(),
+                label: None,
+            },
+        })
+        .collect())
 }
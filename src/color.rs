@@ -0,0 +1,313 @@
+//! Output color styles.
+//!
+//! Each part of the output (matches, selected and context lines, paths, line and
+//! column numbers, separators) has its own [`Style`], configured with `--colors`
+//! specs such as `path:fg:magenta` or `match:bg:0x20,0x20,0x40`, or with the
+//! `GREP_COLORS` environment variable used by GNU grep.
+
+use crate::error::{PergError, Result};
+
+/// A terminal color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    /// One of the eight basic colors, 0 (black) to 7 (white)
+    Ansi(u8),
+    /// An entry of the 256-color palette
+    Ansi256(u8),
+    /// A 24-bit truecolor value
+    Rgb(u8, u8, u8),
+}
+
+const COLOR_NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+impl Color {
+    pub const RED: Color = Color::Ansi(1);
+    pub const GREEN: Color = Color::Ansi(2);
+    pub const YELLOW: Color = Color::Ansi(3);
+    pub const BLUE: Color = Color::Ansi(4);
+    pub const MAGENTA: Color = Color::Ansi(5);
+    pub const CYAN: Color = Color::Ansi(6);
+
+    /// Parse a color name, a 256-color palette number, or an `R,G,B` triple of
+    /// decimal or `0x` hex values (`#RRGGBB` works too)
+    pub fn parse(value: &str) -> Option<Self> {
+        if let Some(idx) = COLOR_NAMES.iter().position(|&name| name == value) {
+            return Some(Color::Ansi(idx as u8));
+        }
+        if let Some(hex) = value.strip_prefix('#').filter(|hex| hex.len() == 6) {
+            let channel = |idx: usize| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok();
+            return Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?));
+        }
+        let channels: Vec<u8> = value.split(',').map(parse_byte).collect::<Option<_>>()?;
+        match channels[..] {
+            [number] => Some(Color::Ansi256(number)),
+            [r, g, b] => Some(Color::Rgb(r, g, b)),
+            _ => None,
+        }
+    }
+
+    /// SGR parameters setting this color as the foreground, or with `base` 40 the background
+    fn sgr(self, base: u8, intense: bool) -> String {
+        match self {
+            Color::Ansi(n) if intense => (base + 60 + n).to_string(),
+            Color::Ansi(n) => (base + n).to_string(),
+            Color::Ansi256(n) => format!("{};5;{}", base + 8, n),
+            Color::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b),
+        }
+    }
+}
+
+fn parse_byte(text: &str) -> Option<u8> {
+    let text = text.trim();
+    match text.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// How one part of the output is displayed; the default leaves it unstyled
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    /// Use the bright variant of a basic foreground color
+    pub intense: bool,
+    pub italic: bool,
+    pub underline: bool,
+    /// Other SGR parameters from `GREP_COLORS`, such as 5 (blink) or 7 (reverse)
+    pub other: Vec<u8>,
+}
+
+impl Style {
+    /// Bold text in a color
+    pub fn bold(fg: Color) -> Self {
+        Self {
+            fg: Some(fg),
+            bold: true,
+            ..Self::default()
+        }
+    }
+
+    /// Style given by the `;`-separated SGR parameters of a `GREP_COLORS` capability
+    pub fn from_sgr(sgr: &str) -> Option<Self> {
+        let mut style = Self::default();
+        let mut params = sgr.split(';').filter(|param| !param.is_empty()).map(|param| param.parse::<u8>().ok());
+        while let Some(param) = params.next() {
+            match param? {
+                0 => style = Self::default(),
+                1 => style.bold = true,
+                3 => style.italic = true,
+                4 => style.underline = true,
+                code @ (30..=37 | 90..=97) => {
+                    style.fg = Some(Color::Ansi(code % 10));
+                    style.intense = code >= 90;
+                }
+                code @ (40..=47 | 100..=107) => style.bg = Some(Color::Ansi(code % 10)),
+                39 => style.fg = None,
+                49 => style.bg = None,
+                code @ (38 | 48) => {
+                    let color = match params.next()?? {
+                        5 => Color::Ansi256(params.next()??),
+                        2 => Color::Rgb(params.next()??, params.next()??, params.next()??),
+                        _ => return None,
+                    };
+                    if code == 38 {
+                        style.fg = Some(color);
+                    } else {
+                        style.bg = Some(color);
+                    }
+                }
+                code => style.other.push(code),
+            }
+        }
+        Some(style)
+    }
+
+    fn sgr(&self) -> String {
+        let mut params = Vec::new();
+        if self.bold {
+            params.push("1".to_string());
+        }
+        if self.italic {
+            params.push("3".to_string());
+        }
+        if self.underline {
+            params.push("4".to_string());
+        }
+        params.extend(self.other.iter().map(u8::to_string));
+        if let Some(fg) = self.fg {
+            params.push(fg.sgr(30, self.intense));
+        }
+        if let Some(bg) = self.bg {
+            params.push(bg.sgr(40, false));
+        }
+        params.join(";")
+    }
+
+    /// Wrap text in the escape sequences for this style
+    pub fn paint(&self, text: &str) -> String {
+        let sgr = self.sgr();
+        if sgr.is_empty() || text.is_empty() {
+            return text.to_string();
+        }
+        format!("\x1b[{}m{}\x1b[0m", sgr, text)
+    }
+}
+
+/// Styles for every part of the output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorScheme {
+    /// File paths and headings
+    pub path: Style,
+    /// Line numbers
+    pub line: Style,
+    /// Column numbers
+    pub column: Style,
+    /// Matched text of the first pattern; other patterns are highlighted in turn
+    /// in green, yellow, blue, magenta and cyan
+    pub matched: Style,
+    /// The rest of a matching line
+    pub selected: Style,
+    /// Context lines
+    pub context: Style,
+    /// Separators between the fields of a line
+    pub separator: Style,
+}
+
+impl Default for ColorScheme {
+    fn default() -> Self {
+        Self {
+            path: Style {
+                fg: Some(Color::MAGENTA),
+                ..Style::default()
+            },
+            line: Style {
+                fg: Some(Color::GREEN),
+                ..Style::default()
+            },
+            column: Style {
+                fg: Some(Color::GREEN),
+                ..Style::default()
+            },
+            matched: Style::bold(Color::RED),
+            selected: Style::default(),
+            context: Style::default(),
+            separator: Style {
+                fg: Some(Color::CYAN),
+                ..Style::default()
+            },
+        }
+    }
+}
+
+impl ColorScheme {
+    fn target(&mut self, name: &str) -> Option<&mut Style> {
+        Some(match name {
+            "path" => &mut self.path,
+            "line" => &mut self.line,
+            "column" => &mut self.column,
+            "match" => &mut self.matched,
+            "selected" => &mut self.selected,
+            "context" => &mut self.context,
+            "separator" => &mut self.separator,
+            _ => return None,
+        })
+    }
+
+    /// Apply a `--colors` spec: `TARGET:none`, `TARGET:fg:COLOR`, `TARGET:bg:COLOR` or
+    /// `TARGET:style:STYLE`, where STYLE is `bold`, `intense`, `italic` or `underline`,
+    /// or one of those prefixed with `no`
+    pub fn apply_spec(&mut self, spec: &str) -> Result<()> {
+        let invalid = |reason: &str| PergError::InvalidColor(format!("{}: {}", spec, reason));
+        let mut parts = spec.splitn(3, ':');
+        let target = parts.next().unwrap_or_default();
+        let style = self.target(target).ok_or_else(|| {
+            invalid("expected path, line, column, match, selected, context or separator")
+        })?;
+
+        match (parts.next(), parts.next()) {
+            (Some("none"), None) => *style = Style::default(),
+            (Some(attribute @ ("fg" | "bg")), Some(value)) => {
+                let color = Color::parse(value).ok_or_else(|| invalid("unknown color"))?;
+                if attribute == "fg" {
+                    style.fg = Some(color);
+                } else {
+                    style.bg = Some(color);
+                }
+            }
+            (Some("style"), Some(value)) => {
+                let (name, on) = match value.strip_prefix("no") {
+                    Some(name) => (name, false),
+                    None => (value, true),
+                };
+                match name {
+                    "bold" => style.bold = on,
+                    "intense" => style.intense = on,
+                    "italic" => style.italic = on,
+                    "underline" => style.underline = on,
+                    _ => return Err(invalid("unknown style")),
+                }
+            }
+            _ => return Err(invalid("expected TARGET:none or TARGET:{fg,bg,style}:VALUE")),
+        }
+        Ok(())
+    }
+
+    /// Apply the capabilities of a `GREP_COLORS` value such as `ms=01;31:fn=35:ln=32:se=36`.
+    ///
+    /// `mt` and `ms` set the match style, `sl` and `cx` the selected and context line
+    /// styles, and `fn`, `ln` and `se` the path, line number and separator styles.
+    /// Other capabilities and malformed values are ignored, as GNU grep does.
+    pub fn apply_grep_colors(&mut self, value: &str) {
+        for capability in value.split(':') {
+            let Some((name, sgr)) = capability.split_once('=') else {
+                continue;
+            };
+            let style = match name {
+                "mt" | "ms" => &mut self.matched,
+                "sl" => &mut self.selected,
+                "cx" => &mut self.context,
+                "fn" => &mut self.path,
+                "ln" => &mut self.line,
+                "se" => &mut self.separator,
+                _ => continue,
+            };
+            if let Some(parsed) = Style::from_sgr(sgr) {
+                *style = parsed;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_specs() {
+        let mut colors = ColorScheme::default();
+        colors.apply_spec("path:fg:blue").unwrap();
+        colors.apply_spec("path:style:underline").unwrap();
+        colors.apply_spec("match:none").unwrap();
+        colors.apply_spec("match:bg:0x20,0x20,0x40").unwrap();
+        colors.apply_spec("line:fg:208").unwrap();
+        assert_eq!(colors.path.paint("a.txt"), "\x1b[4;34ma.txt\x1b[0m");
+        assert_eq!(colors.matched.paint("foo"), "\x1b[48;2;32;32;64mfoo\x1b[0m");
+        assert_eq!(colors.line.paint("3"), "\x1b[38;5;208m3\x1b[0m");
+
+        for spec in ["title:fg:red", "path:fg:purple", "path:style:blink", "path:fg", "path"] {
+            assert!(colors.apply_spec(spec).is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn test_grep_colors() {
+        let mut colors = ColorScheme::default();
+        colors.apply_grep_colors("ms=01;32:fn=:ln=38;5;99:sl=7:bn=32:ne");
+        assert_eq!(colors.matched, Style::bold(Color::GREEN));
+        assert_eq!(colors.path.paint("a.txt"), "a.txt");
+        assert_eq!(colors.line.fg, Some(Color::Ansi256(99)));
+        assert_eq!(colors.selected.paint("x"), "\x1b[7mx\x1b[0m");
+    }
+}
@@ -11,6 +11,16 @@ pub enum PergError {
     FileNotFound(String),
     /// Invalid pattern
     InvalidPattern(String),
+    /// Input too large to be searched in memory
+    InputTooLarge(String),
+    /// Unrecognized date or time value
+    InvalidTime(String),
+    /// Malformed structured input such as CSV
+    Parse(String),
+    /// Malformed `--format` template
+    InvalidTemplate(String),
+    /// Malformed `--colors` spec
+    InvalidColor(String),
 }
 
 impl fmt::Display for PergError {
@@ -20,6 +30,11 @@ impl fmt::Display for PergError {
             PergError::Regex(err) => write!(f, "Regex error: {}", err),
             PergError::FileNotFound(path) => write!(f, "File not found: {}", path),
             PergError::InvalidPattern(pattern) => write!(f, "Invalid pattern: {}", pattern),
+            PergError::InputTooLarge(path) => write!(f, "Input too large to search in memory: {}", path),
+            PergError::InvalidTime(value) => write!(f, "Invalid time: {}", value),
+            PergError::Parse(message) => write!(f, "Parse error: {}", message),
+            PergError::InvalidTemplate(message) => write!(f, "Invalid output format: {}", message),
+            PergError::InvalidColor(spec) => write!(f, "Invalid color spec: {}", spec),
         }
     }
 }
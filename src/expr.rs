@@ -0,0 +1,328 @@
+//! Boolean query expressions over several patterns, in the style of `git grep`:
+//!
+//! ```text
+//! -e foo --and ( -e bar --or -e baz ) --not -e test
+//! ```
+//!
+//! `--not` binds tightest, then `--and`, then `--or`. Patterns written next to
+//! each other without an operator are joined with `--or`, like `-e a -e b` in
+//! grep, while a `--not` without an operator is joined with `--and`, so the
+//! example above reads "foo and (bar or baz) but not test".
+
+use crate::error::{PergError, Result};
+use regex::Regex;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// One element of an expression as given on the command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    /// A pattern, with the label `--pattern-id` shows for it
+    Pattern { pattern: String, label: Option<String> },
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+/// Parsed expression; terms refer to patterns by index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Term(usize),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+/// An expression together with the patterns its terms refer to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expression {
+    pub expr: Expr,
+    pub patterns: Vec<String>,
+    /// Label of each pattern: given in the pattern file, or its 1-based position
+    pub labels: Vec<String>,
+}
+
+impl Expression {
+    /// Parse tokens in command-line order
+    pub fn parse(tokens: Vec<Token>) -> Result<Self> {
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            patterns: Vec::new(),
+            labels: Vec::new(),
+        };
+        let expr = parser.or_expr()?;
+        match parser.tokens.get(parser.position) {
+            None => Ok(Self {
+                expr,
+                patterns: parser.patterns,
+                labels: parser.labels,
+            }),
+            Some(Token::Close) => Err(invalid("unmatched ')'")),
+            Some(token) => Err(invalid(&format!("unexpected {}", describe(token)))),
+        }
+    }
+}
+
+/// Recursive descent parser over the token list
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    patterns: Vec<String>,
+    labels: Vec<String>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn or_expr(&mut self) -> Result<Expr> {
+        let mut expr = self.and_expr()?;
+        loop {
+            match self.peek() {
+                Some(Token::Or) => self.position += 1,
+                // Adjacent patterns are implicitly joined with --or
+                Some(Token::Pattern { .. } | Token::Open) => {}
+                _ => return Ok(expr),
+            }
+            expr = Expr::Or(Box::new(expr), Box::new(self.and_expr()?));
+        }
+    }
+
+    fn and_expr(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.position += 1,
+                Some(Token::Not) => {}
+                _ => return Ok(expr),
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        let token = self.peek().cloned();
+        self.position += 1;
+        match token {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let expr = self.or_expr()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(invalid("unmatched '('"));
+                }
+                self.position += 1;
+                Ok(expr)
+            }
+            Some(Token::Pattern { pattern, label }) => {
+                self.patterns.push(pattern);
+                self.labels.push(label.unwrap_or_else(|| self.patterns.len().to_string()));
+                Ok(Expr::Term(self.patterns.len() - 1))
+            }
+            Some(token) => Err(invalid(&format!("expected a pattern before {}", describe(&token)))),
+            None => Err(invalid("expected a pattern at the end of the expression")),
+        }
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Pattern { pattern, .. } => format!("pattern '{}'", pattern),
+        Token::And => "--and".to_string(),
+        Token::Or => "--or".to_string(),
+        Token::Not => "--not".to_string(),
+        Token::Open => "'('".to_string(),
+        Token::Close => "')'".to_string(),
+    }
+}
+
+fn invalid(message: &str) -> PergError {
+    PergError::InvalidPattern(format!("invalid expression: {}", message))
+}
+
+/// Evaluates an expression against a line, one compiled regex per term
+#[derive(Debug, Clone)]
+pub struct ExpressionMatcher {
+    expr: Expr,
+    terms: Vec<Regex>,
+    labels: Vec<String>,
+}
+
+impl ExpressionMatcher {
+    pub fn new(expression: &Expression, terms: Vec<Regex>) -> Self {
+        Self {
+            expr: expression.expr.clone(),
+            terms,
+            labels: expression.labels.clone(),
+        }
+    }
+
+    /// Whether the expression holds for `text`, with the spans of every term that
+    /// matched outside a `--not`, sorted and merged for highlighting
+    pub fn find(&self, text: &str) -> Option<Vec<Range<usize>>> {
+        let term_spans: Vec<Vec<Range<usize>>> = self
+            .terms
+            .iter()
+            .map(|regex| regex.find_iter(text).map(|mat| mat.range()).collect())
+            .collect();
+        if !evaluate(&self.expr, &term_spans) {
+            return None;
+        }
+
+        let mut spans: Vec<Range<usize>> = self
+            .positive_terms()
+            .into_iter()
+            .flat_map(|term| term_spans[term].clone())
+            .collect();
+        spans.sort_by_key(|span| (span.start, span.end));
+
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(spans.len());
+        for span in spans {
+            match merged.last_mut() {
+                Some(last) if span.start < last.end => last.end = last.end.max(span.end),
+                _ => merged.push(span),
+            }
+        }
+        Some(merged)
+    }
+
+    /// Terms that appear outside any `--not`
+    pub fn positive_terms(&self) -> Vec<usize> {
+        let mut terms = Vec::new();
+        positive_terms(&self.expr, false, &mut terms);
+        terms
+    }
+
+    /// Whether one term's pattern matches `text`
+    pub fn term_matches(&self, term: usize, text: &str) -> bool {
+        self.terms[term].is_match(text)
+    }
+
+    /// Number of patterns in the expression
+    pub fn term_count(&self) -> usize {
+        self.terms.len()
+    }
+
+    /// Labels of the patterns, by term
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    /// Byte spans of one term's matches in `text`
+    pub fn term_spans(&self, term: usize, text: &str) -> Vec<Range<usize>> {
+        self.terms[term].find_iter(text).map(|mat| mat.range()).collect()
+    }
+}
+
+/// Number of selected lines each pattern matched, across all searched inputs
+#[derive(Debug)]
+pub struct PatternStats {
+    labels: Vec<String>,
+    patterns: Vec<String>,
+    lines: Vec<AtomicUsize>,
+}
+
+impl PatternStats {
+    pub fn new(expression: &Expression) -> Self {
+        Self {
+            labels: expression.labels.clone(),
+            patterns: expression.patterns.clone(),
+            lines: expression.patterns.iter().map(|_| AtomicUsize::new(0)).collect(),
+        }
+    }
+
+    /// Count one more line matched by a pattern
+    pub fn record(&self, term: usize) {
+        self.lines[term].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Table of hit counts per pattern, including patterns that never matched
+    pub fn table(&self) -> String {
+        let label_width = self.labels.iter().map(String::len).max().unwrap_or(0).max("PATTERN".len());
+        let mut table = format!("{:<label_width$}  {:>8}  REGEX\n", "PATTERN", "LINES");
+        for ((label, pattern), lines) in self.labels.iter().zip(&self.patterns).zip(&self.lines) {
+            let lines = lines.load(Ordering::Relaxed);
+            table.push_str(&format!("{:<label_width$}  {:>8}  {}\n", label, lines, pattern));
+        }
+        table
+    }
+}
+
+fn evaluate(expr: &Expr, term_spans: &[Vec<Range<usize>>]) -> bool {
+    match expr {
+        Expr::Term(term) => !term_spans[*term].is_empty(),
+        Expr::Not(inner) => !evaluate(inner, term_spans),
+        Expr::And(left, right) => evaluate(left, term_spans) && evaluate(right, term_spans),
+        Expr::Or(left, right) => evaluate(left, term_spans) || evaluate(right, term_spans),
+    }
+}
+
+/// Collect the terms that appear under an even number of `--not`s
+fn positive_terms(expr: &Expr, negated: bool, terms: &mut Vec<usize>) {
+    match expr {
+        Expr::Term(term) if !negated => terms.push(*term),
+        Expr::Term(_) => {}
+        Expr::Not(inner) => positive_terms(inner, !negated, terms),
+        Expr::And(left, right) | Expr::Or(left, right) => {
+            positive_terms(left, negated, terms);
+            positive_terms(right, negated, terms);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(spec: &str) -> Vec<Token> {
+        spec.split_whitespace()
+            .map(|word| match word {
+                "--and" => Token::And,
+                "--or" => Token::Or,
+                "--not" => Token::Not,
+                "(" => Token::Open,
+                ")" => Token::Close,
+                pattern => Token::Pattern {
+                    pattern: pattern.to_string(),
+                    label: None,
+                },
+            })
+            .collect()
+    }
+
+    fn build(spec: &str) -> ExpressionMatcher {
+        let expression = Expression::parse(tokens(spec)).unwrap();
+        let terms = expression.patterns.iter().map(|pattern| Regex::new(pattern).unwrap()).collect();
+        ExpressionMatcher::new(&expression, terms)
+    }
+
+    #[test]
+    fn test_precedence_and_highlighting() {
+        let matcher = build("foo --and ( bar --or baz ) --not test");
+        assert_eq!(matcher.find("foo baz"), Some(vec![0..3, 4..7]));
+        assert_eq!(matcher.find("foo qux"), None);
+        assert_eq!(matcher.find("foo bar test"), None);
+
+        // `a b --and c` is `a --or (b --and c)`
+        let matcher = build("a b --and c");
+        assert!(matcher.find("a").is_some());
+        assert!(matcher.find("b").is_none());
+
+        // `--not` on its own is only useful as "but not"
+        let matcher = build("a --or b --not c");
+        assert!(matcher.find("a c").is_some());
+        assert!(matcher.find("b c").is_none());
+    }
+
+
+    #[test]
+    fn test_rejects_malformed_expressions() {
+        for spec in ["foo --and", "( foo", "foo )", "--or foo", "foo --and --or bar", ""] {
+            let err = Expression::parse(tokens(spec)).unwrap_err();
+            assert!(matches!(err, PergError::InvalidPattern(_)), "{}", spec);
+        }
+    }
+}
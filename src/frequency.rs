@@ -0,0 +1,124 @@
+//! Match frequency tables for `--frequency` and `--unique`.
+//!
+//! Counts distinct match texts (or capture groups) across every searched input,
+//! like `perg -o PATTERN | sort | uniq -c | sort -rn`, keeping only the match
+//! texts rather than the lines they came from.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// How the collected values are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrequencyMode {
+    /// Values with their counts, most frequent first
+    Counts,
+    /// Distinct values, in order of first appearance
+    Unique,
+}
+
+/// Distinct values seen across all inputs, with per-input counts
+#[derive(Debug)]
+pub struct Frequencies {
+    pub mode: FrequencyMode,
+    /// Report at most this many values
+    pub top: Option<usize>,
+    /// Break each value's count down by input
+    pub per_file: bool,
+    tally: Mutex<Tally>,
+}
+
+#[derive(Debug, Default)]
+struct Tally {
+    /// Index of each value in `values`
+    index: HashMap<String, usize>,
+    /// Values in order of first appearance, with their total count
+    values: Vec<(String, usize)>,
+    /// Count of each value (by index) per input, in order of first appearance
+    files: Vec<(String, HashMap<usize, usize>)>,
+}
+
+impl Frequencies {
+    pub fn new(mode: FrequencyMode) -> Self {
+        Self {
+            mode,
+            top: None,
+            per_file: false,
+            tally: Mutex::new(Tally::default()),
+        }
+    }
+
+    /// Count the values found in one input; `name` is the path, or `(standard input)`
+    pub fn add<'a>(&self, name: &str, values: impl IntoIterator<Item = &'a str>) {
+        let mut tally = self.tally.lock().expect("frequency tally lock");
+        let tally = &mut *tally;
+        let mut file_counts = HashMap::new();
+        for value in values {
+            let idx = match tally.index.get(value) {
+                Some(&idx) => idx,
+                None => {
+                    tally.values.push((value.to_string(), 0));
+                    tally.index.insert(value.to_string(), tally.values.len() - 1);
+                    tally.values.len() - 1
+                }
+            };
+            tally.values[idx].1 += 1;
+            *file_counts.entry(idx).or_insert(0) += 1;
+        }
+        if self.per_file && !file_counts.is_empty() {
+            tally.files.push((name.to_string(), file_counts));
+        }
+    }
+
+    /// The report printed at the end of the run, one value per line
+    pub fn report(&self) -> String {
+        let tally = self.tally.lock().expect("frequency tally lock");
+        let mut order: Vec<usize> = (0..tally.values.len()).collect();
+        if self.mode == FrequencyMode::Counts {
+            // Stable, so values with equal counts keep their first-seen order
+            order.sort_by_key(|&idx| std::cmp::Reverse(tally.values[idx].1));
+        }
+        order.truncate(self.top.unwrap_or(usize::MAX));
+
+        let mut report = String::new();
+        for idx in order {
+            let (value, count) = &tally.values[idx];
+            match self.mode {
+                FrequencyMode::Counts => report.push_str(&format!("{:>7} {}\n", count, value)),
+                FrequencyMode::Unique => report.push_str(&format!("{}\n", value)),
+            }
+            if self.per_file {
+                for (name, counts) in &tally.files {
+                    if let Some(count) = counts.get(&idx) {
+                        report.push_str(&format!("{:>15} {}\n", count, name));
+                    }
+                }
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_sorted_with_per_file_breakdown() {
+        let mut frequencies = Frequencies::new(FrequencyMode::Counts);
+        frequencies.per_file = true;
+        frequencies.top = Some(2);
+        frequencies.add("a.log", ["bob", "alice", "alice"]);
+        frequencies.add("b.log", ["alice", "carol"]);
+
+        assert_eq!(
+            frequencies.report(),
+            concat!(
+                "      3 alice\n",
+                "              2 a.log\n",
+                "              1 b.log\n",
+                "      1 bob\n",
+                "              1 a.log\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,188 @@
+//! Approximate literal matching.
+//!
+//! Finds substrings within a maximum edit distance (insertions, deletions and
+//! substitutions) of a literal pattern, agrep-style. The scan uses Myers'
+//! bit-parallel algorithm, advancing one machine word per input character
+//! instead of filling a dynamic programming column.
+
+use crate::error::{PergError, Result};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Longest pattern the bit-parallel scan supports: one bit per pattern character
+pub const MAX_FUZZY_PATTERN_CHARS: usize = 64;
+
+/// Finds approximate occurrences of a literal pattern
+#[derive(Debug, Clone)]
+pub struct FuzzyMatcher {
+    pattern: Vec<char>,
+    max_distance: usize,
+    ignore_case: bool,
+    /// Bit `i` of `masks[c]` is set when the pattern's `i`th character is `c`
+    masks: HashMap<char, u64>,
+}
+
+impl FuzzyMatcher {
+    pub fn new(pattern: &str, max_distance: usize, ignore_case: bool) -> Result<Self> {
+        let pattern: Vec<char> = pattern.chars().map(|c| fold(c, ignore_case)).collect();
+        if pattern.len() > MAX_FUZZY_PATTERN_CHARS {
+            return Err(PergError::InvalidPattern(format!(
+                "fuzzy patterns are limited to {} characters",
+                MAX_FUZZY_PATTERN_CHARS
+            )));
+        }
+        if max_distance >= pattern.len() {
+            return Err(PergError::InvalidPattern(format!(
+                "edit distance {} would match anywhere; it must be shorter than the pattern",
+                max_distance
+            )));
+        }
+
+        let mut masks = HashMap::new();
+        for (idx, &c) in pattern.iter().enumerate() {
+            *masks.entry(c).or_insert(0) |= 1 << idx;
+        }
+        Ok(Self {
+            pattern,
+            max_distance,
+            ignore_case,
+            masks,
+        })
+    }
+
+    /// Byte spans of non-overlapping approximate occurrences in `text`
+    pub fn find_spans(&self, text: &str) -> Vec<Range<usize>> {
+        let chars: Vec<(usize, char)> = text.char_indices().collect();
+        let byte_at = |idx: usize| chars.get(idx).map_or(text.len(), |&(offset, _)| offset);
+        let last = 1u64 << (self.pattern.len() - 1);
+
+        let (mut positive, mut negative) = (u64::MAX, 0u64);
+        let mut score = self.pattern.len();
+        // Best end (in characters) and distance of the occurrence being extended
+        let mut pending: Option<(usize, usize)> = None;
+        let mut covered = 0;
+        let mut spans = Vec::new();
+
+        for (idx, &(_, c)) in chars.iter().enumerate() {
+            let eq = self.masks.get(&fold(c, self.ignore_case)).copied().unwrap_or(0);
+            let xv = eq | negative;
+            let xh = ((eq & positive).wrapping_add(positive) ^ positive) | eq;
+            let mut horizontal_positive = negative | !(xh | positive);
+            let mut horizontal_negative = positive & xh;
+            if horizontal_positive & last != 0 {
+                score += 1;
+            } else if horizontal_negative & last != 0 {
+                score -= 1;
+            }
+            horizontal_positive <<= 1;
+            horizontal_negative <<= 1;
+            positive = horizontal_negative | !(xv | horizontal_positive);
+            negative = horizontal_positive & xv;
+
+            // Keep extending while the distance improves, then report the best end
+            match pending {
+                Some((_, distance)) if score < distance => pending = Some((idx + 1, score)),
+                Some(_) if score <= self.max_distance => {}
+                None if score <= self.max_distance => pending = Some((idx + 1, score)),
+                _ => {}
+            }
+            if let Some((end, distance)) = pending {
+                if score > distance || idx + 1 == chars.len() {
+                    pending = None;
+                    let start = self.best_start(&chars, end, distance);
+                    if start >= covered {
+                        spans.push(byte_at(start)..byte_at(end));
+                        covered = end;
+                    }
+                }
+            }
+        }
+        spans
+    }
+
+    /// Edit distance between the pattern and a matched substring
+    pub fn distance(&self, matched: &str) -> usize {
+        let text: Vec<char> = matched.chars().map(|c| fold(c, self.ignore_case)).collect();
+        let mut row: Vec<usize> = (0..=text.len()).collect();
+        for (i, &p) in self.pattern.iter().enumerate() {
+            let mut diagonal = row[0];
+            row[0] = i + 1;
+            for (j, &t) in text.iter().enumerate() {
+                let substitution = diagonal + usize::from(p != t);
+                diagonal = row[j + 1];
+                row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+            }
+        }
+        row[text.len()]
+    }
+
+    /// Latest start of an occurrence ending at character `end` with the given distance.
+    ///
+    /// Aligns the reversed pattern against the text read backwards from `end`; only
+    /// a window of `pattern + max_distance` characters can be part of the occurrence.
+    fn best_start(&self, chars: &[(usize, char)], end: usize, distance: usize) -> usize {
+        let window = (self.pattern.len() + self.max_distance).min(end);
+        let text: Vec<char> = chars[end - window..end]
+            .iter()
+            .rev()
+            .map(|&(_, c)| fold(c, self.ignore_case))
+            .collect();
+
+        // column[t]: distance between the reversed pattern prefix and the last `t` characters
+        let mut column: Vec<usize> = (0..=text.len()).collect();
+        for (i, &p) in self.pattern.iter().rev().enumerate() {
+            let mut diagonal = column[0];
+            column[0] = i + 1;
+            for (t, &c) in text.iter().enumerate() {
+                let substitution = diagonal + usize::from(p != c);
+                diagonal = column[t + 1];
+                column[t + 1] = substitution.min(column[t] + 1).min(diagonal + 1);
+            }
+        }
+        let length = column.iter().position(|&d| d <= distance).unwrap_or(window);
+        end - length
+    }
+}
+
+/// Fold a character for comparison
+fn fold(c: char, ignore_case: bool) -> char {
+    if ignore_case {
+        c.to_lowercase().next().unwrap_or(c)
+    } else {
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn found<'a>(matcher: &FuzzyMatcher, text: &'a str) -> Vec<(&'a str, usize)> {
+        matcher
+            .find_spans(text)
+            .into_iter()
+            .map(|span| (&text[span.clone()], matcher.distance(&text[span])))
+            .collect()
+    }
+
+    #[test]
+    fn test_finds_edits() {
+        let matcher = FuzzyMatcher::new("receive", 1, false).unwrap();
+        assert_eq!(
+            found(&matcher, "we recive, receive and reciive but not rcv"),
+            vec![("recive", 1), ("receive", 0), ("reciive", 1)]
+        );
+
+        let matcher = FuzzyMatcher::new("Straße", 1, true).unwrap();
+        assert_eq!(found(&matcher, "STRASE and strasse"), vec![("STRASE", 1)]);
+
+        let matcher = FuzzyMatcher::new("abcd", 1, false).unwrap();
+        assert_eq!(found(&matcher, "abcdabxd-abd"), vec![("abcd", 0), ("abxd", 1), ("abd", 1)]);
+    }
+
+    #[test]
+    fn test_rejects_unusable_patterns() {
+        assert!(FuzzyMatcher::new("ab", 2, false).is_err());
+        assert!(FuzzyMatcher::new(&"x".repeat(65), 1, false).is_err());
+    }
+}
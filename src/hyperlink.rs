@@ -0,0 +1,156 @@
+//! OSC 8 hyperlinks on file paths and line numbers.
+//!
+//! Links are built from a URL template such as `file://{host}{path}` or
+//! `vscode://file{path}:{line}:{column}`, where `{path}` is the absolute,
+//! percent-encoded path of the file.
+
+use crate::error::{PergError, Result};
+use std::path::Path;
+
+/// Named URL templates accepted by `--hyperlink-format`
+const ALIASES: [(&str, &str); 8] = [
+    ("default", "file://{host}{path}"),
+    ("file", "file://{host}{path}"),
+    ("kitty", "file://{host}{path}#{line}"),
+    ("vscode", "vscode://file{path}:{line}:{column}"),
+    ("vscode-insiders", "vscode-insiders://file{path}:{line}:{column}"),
+    ("cursor", "cursor://file{path}:{line}:{column}"),
+    ("macvim", "mvim://open?url=file://{path}&line={line}&column={column}"),
+    ("textmate", "txmt://open?url=file://{path}&line={line}&column={column}"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Host,
+    Path,
+    Line,
+    Column,
+}
+
+/// URL template for hyperlinks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HyperlinkFormat {
+    parts: Vec<Part>,
+    host: String,
+}
+
+impl HyperlinkFormat {
+    /// Parse a URL template with `{host}`, `{path}`, `{line}` and `{column}` placeholders,
+    /// or one of the aliases `default`, `file`, `kitty`, `vscode`, `vscode-insiders`,
+    /// `cursor`, `macvim` and `textmate`
+    pub fn parse(spec: &str) -> Result<Self> {
+        let invalid = |reason: String| PergError::InvalidTemplate(format!("hyperlink format {}: {}", spec, reason));
+        let template = ALIASES.iter().find(|(name, _)| *name == spec).map_or(spec, |(_, template)| template);
+
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                parts.push(Part::Literal(rest[..open].to_string()));
+            }
+            let close = rest[open..].find('}').ok_or_else(|| invalid("unclosed placeholder".to_string()))? + open;
+            parts.push(match &rest[open + 1..close] {
+                "host" => Part::Host,
+                "path" => Part::Path,
+                "line" => Part::Line,
+                "column" => Part::Column,
+                name => return Err(invalid(format!("unknown placeholder {{{}}}", name))),
+            });
+            rest = &rest[close + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        if !parts.contains(&Part::Path) {
+            return Err(invalid("missing {path}".to_string()));
+        }
+
+        Ok(Self {
+            parts,
+            host: hostname(),
+        })
+    }
+
+    /// URL for a file path, relative to the current directory or absolute, and an
+    /// optional position in it; `None` when the template needs a position that is missing
+    pub fn url(&self, path: &str, line: Option<usize>, column: Option<usize>) -> Option<String> {
+        let absolute = std::path::absolute(Path::new(path)).ok()?;
+        let mut url = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => url.push_str(text),
+                Part::Host => url.push_str(&self.host),
+                Part::Path => url.push_str(&encode_path(&absolute.to_string_lossy())),
+                Part::Line => url.push_str(&line?.to_string()),
+                // Without a match, point at the start of the line
+                Part::Column => url.push_str(&column.or(line.map(|_| 1))?.to_string()),
+            }
+        }
+        Some(url)
+    }
+}
+
+/// Wrap text in an OSC 8 hyperlink
+pub fn link(url: &str, text: &str) -> String {
+    format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
+}
+
+/// Percent-encode a path for use in a URL, keeping `/` separators; Windows paths
+/// get a leading `/` before the drive letter
+fn encode_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut encoded = String::with_capacity(path.len());
+    if !path.starts_with('/') {
+        encoded.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'.' | b'_' | b'~' | b':' => {
+                encoded.push(char::from(byte))
+            }
+            byte => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Name of this machine for `{host}`, or empty (meaning the local host) when unknown
+fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hyperlink_urls() {
+        let format = HyperlinkFormat::parse("vscode").unwrap();
+        assert_eq!(
+            format.url("/src/my file.rs", Some(3), Some(7)).as_deref(),
+            Some("vscode://file/src/my%20file.rs:3:7")
+        );
+        assert_eq!(format.url("/src/a.rs", Some(3), None).as_deref(), Some("vscode://file/src/a.rs:3:1"));
+        assert_eq!(format.url("/src/a.rs", None, None), None);
+
+        let format = HyperlinkFormat::parse("file://{path}").unwrap();
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(
+            format.url("a.rs", None, None),
+            Some(format!("file://{}", encode_path(&cwd.join("a.rs").to_string_lossy())))
+        );
+    }
+
+    #[test]
+    fn test_invalid_hyperlink_formats() {
+        for spec in ["file://{host}{path", "file://{path}:{col}", "vscode://file"] {
+            assert!(HyperlinkFormat::parse(spec).is_err(), "{}", spec);
+        }
+    }
+}
@@ -15,20 +15,76 @@
 //! - Limit number of matches (`-m` flag)
 //! - Extended regular expressions (`-E` flag)
 //! - Colorized output (`--color` flag)
+//! - Multiline matching across line boundaries (`-U` flag)
+//! - NUL-terminated records and filenames (`-z`/`-Z` flags)
+//! - Custom record separators and paragraph mode (`--record-separator`/`--paragraph`)
+//! - Multi-line log records grouped by their first line (`--record-start`)
+//! - Time-range filtering for log files (`--since`/`--until`)
+//! - Field-aware search in CSV/TSV files (`--csv-column`)
+//! - Structural search in JSON and JSON Lines by key path (`--json-path`)
+//! - YAML and TOML key-path aware search (`--key-path`)
+//! - Numeric range matching (`--number-range`)
+//! - IP address and CIDR matching (`--cidr`)
+//! - Approximate matching within an edit distance (`--fuzzy`)
+//! - JSON Lines output (`--json`)
+//! - Unicode normalization and diacritic-insensitive matching (`--normalize`/`--ignore-diacritics`)
+//! - Column numbers of matches (`--column`)
+//! - Boolean queries over several patterns (`-e`, `--and`, `--or`, `--not`)
+//! - Files matching all patterns (`--all-match`)
+//! - Proximity search between two patterns (`--near`/`--not-near`)
+//! - Pattern files, per-pattern attribution and statistics (`-f`, `--pattern-id`, `--pattern-stats`)
+//! - Match frequency tables (`--frequency`/`--unique`)
+//! - Capture groups as CSV, TSV or JSON rows (`--extract`)
+//! - Output line templates (`--format`)
+//! - Per-file headings on terminals (`--heading`/`--no-heading`)
+//! - Configurable output colors (`--colors`, `GREP_COLORS`)
+//! - Clickable OSC 8 hyperlinks on paths and line numbers (`--hyperlink-format`)
+//! - Printing every line with matches highlighted (`--passthru`)
+//! - Omitting or previewing over-long lines (`--max-columns`/`--max-columns-preview`)
+//! - Group separators between context blocks and configurable field separators
 //! - Invert match (`-v` flag)
 //! - Files with/without matches listing (`-l`/`-L` flags)
 //! - Reading from stdin when no file paths are provided
 
 pub mod cli;
+pub mod color;
 pub mod error;
+pub mod expr;
+pub mod extract;
+pub mod frequency;
+pub mod fuzzy;
+pub mod hyperlink;
+pub mod matcher;
+pub mod network;
+pub mod normalize;
+pub mod numeric;
 pub mod search;
+pub mod structured;
+pub mod tabular;
+pub mod template;
+pub mod timestamp;
 
 // Declare external dependencies for use in modules
+extern crate chrono;
 extern crate clap;
 extern crate regex;
 extern crate walkdir;
 
 // Re-export commonly used types
 pub use cli::Args;
+pub use color::{ColorScheme, Style};
 pub use error::{PergError, Result};
+pub use expr::{Expression, ExpressionMatcher, PatternStats};
+pub use extract::{ExtractFormat, Extractor};
+pub use frequency::{Frequencies, FrequencyMode};
+pub use fuzzy::FuzzyMatcher;
+pub use hyperlink::HyperlinkFormat;
+pub use matcher::Matcher;
+pub use network::{Network, NetworkMatcher};
+pub use normalize::{NormalizationForm, Normalizer};
+pub use numeric::{NumberMatcher, NumberRange};
 pub use search::{search_file, search_paths, search_stdin, SearchConfig};
+pub use structured::{KeyPath, StructuredFormat};
+pub use tabular::CsvOptions;
+pub use template::Template;
+pub use timestamp::TimeFilter;
@@ -1,15 +1,32 @@
-use clap::Parser;
-use perg::cli::Args;
+use clap::{CommandFactory, FromArgMatches};
+use perg::cli::{self, Args};
 use perg::error::PergError;
-use perg::search::{search_paths, search_stdin, SearchConfig};
+use perg::expr::{Expression, PatternStats, Token};
+use perg::extract::{ExtractFormat, Extractor};
+use perg::frequency::{Frequencies, FrequencyMode};
+use perg::fuzzy::FuzzyMatcher;
+use perg::hyperlink::HyperlinkFormat;
+use perg::network::Network;
+use perg::normalize::{NormalizationForm, Normalizer};
+use perg::numeric::NumberRange;
+use perg::search::{search_paths, search_stdin, GroupSeparator, Headings, Proximity, SearchConfig, PARAGRAPH_SEPARATOR};
+use perg::structured::{KeyPath, StructuredFormat};
+use perg::template::{Placeholder, Template};
+use perg::tabular::CsvOptions;
+use perg::timestamp::TimeFilter;
+use std::io::IsTerminal;
 use std::process;
+use std::sync::Arc;
 
 /// Main entry point for the perg command-line tool.
 /// 
 /// The tool supports reading from files or stdin, with various search options
 /// including regular expressions, context lines, counting, and more.
 fn main() {
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    args.shift_positionals();
+    let has_expression = args.has_expression();
 
     // Validate arguments
     if args.files_with_matches && args.files_without_match {
@@ -18,8 +35,8 @@ fn main() {
     }
 
     // Create search configuration
-    let config = SearchConfig::new(
-        args.pattern,
+    let mut config = SearchConfig::new(
+        args.pattern.unwrap_or_default(),
         args.ignore_case,
         args.line_number,
         args.with_filename,
@@ -35,6 +52,219 @@ fn main() {
         args.extended_regexp,
         args.color,
     );
+    config.multiline = args.multiline;
+    config.multiline_dotall = args.multiline_dotall;
+    config.null_data = args.null_data;
+    config.null = args.null;
+    config.passthru = args.passthru;
+    config.max_columns = args.max_columns.filter(|&width| width > 0);
+    config.max_columns_preview = args.max_columns_preview;
+    config.group_separator = match (args.no_group_separator, args.group_separator) {
+        (true, _) => None,
+        (false, Some(separator)) => Some(Arc::new(GroupSeparator::new(separator))),
+        (false, None) => config.group_separator,
+    };
+    if let Some(separator) = args.field_match_separator {
+        config.match_separator = separator;
+    }
+    if let Some(separator) = args.field_context_separator {
+        config.context_separator = separator;
+    }
+    config.record_separator = if args.paragraph {
+        Some(PARAGRAPH_SEPARATOR.to_string())
+    } else {
+        args.record_separator
+    };
+    config.record_start = args.record_start;
+    if args.since.is_some() || args.until.is_some() {
+        match TimeFilter::new(args.since.as_deref(), args.until.as_deref(), args.time_format.as_deref()) {
+            Ok(filter) => config.time_filter = Some(filter),
+            Err(err) => {
+                eprintln!("perg: {}", err);
+                process::exit(2);
+            }
+        }
+    }
+    if let Some(column) = args.csv_column {
+        let delimiter = match (args.tsv, args.csv_delimiter) {
+            (true, _) => b'\t',
+            (false, Some(delimiter)) if delimiter.is_ascii() => delimiter as u8,
+            (false, Some(delimiter)) => {
+                eprintln!("perg: CSV delimiter must be a single ASCII character: {}", delimiter);
+                process::exit(2);
+            }
+            (false, None) => b',',
+        };
+        let mut options = CsvOptions::new(column, delimiter);
+        options.has_header = !args.csv_no_header;
+        options.output_columns = args.csv_output;
+        config.csv = Some(options);
+    }
+    if let Some(path) = &args.json_path {
+        match KeyPath::parse(path) {
+            Ok(path) => config.json_path = Some(path),
+            Err(err) => {
+                eprintln!("perg: {}", err);
+                process::exit(2);
+            }
+        }
+        config.json_pretty = args.json_pretty;
+    }
+    if let Some(path) = &args.key_path {
+        match KeyPath::parse(path) {
+            Ok(path) => config.key_path = Some(path),
+            Err(err) => {
+                eprintln!("perg: {}", err);
+                process::exit(2);
+            }
+        }
+        config.input_format = args.input_format.as_deref().and_then(StructuredFormat::from_name);
+    }
+    if let Some(range) = &args.number_range {
+        match NumberRange::parse(range) {
+            Ok(range) => config.number_range = Some(range),
+            Err(err) => {
+                eprintln!("perg: {}", err);
+                process::exit(2);
+            }
+        }
+        config.number_key = args.number_key;
+    }
+    if let Some(max_distance) = args.fuzzy {
+        if let Err(err) = FuzzyMatcher::new(&config.pattern, max_distance, config.ignore_case) {
+            eprintln!("perg: {}", err);
+            process::exit(2);
+        }
+        config.fuzzy = Some(max_distance);
+    }
+    config.json = args.json;
+    if let Ok(grep_colors) = std::env::var("GREP_COLORS") {
+        config.colors.apply_grep_colors(&grep_colors);
+    }
+    for spec in &args.colors {
+        if let Err(err) = config.colors.apply_spec(spec) {
+            eprintln!("perg: {}", err);
+            process::exit(2);
+        }
+    }
+    if let Some(format) = &args.hyperlink_format {
+        match HyperlinkFormat::parse(format) {
+            Ok(format) => config.hyperlinks = Some(format),
+            Err(err) => {
+                eprintln!("perg: {}", err);
+                process::exit(2);
+            }
+        }
+    }
+    if let Some(format) = &args.format {
+        match Template::parse(format) {
+            Ok(template) => config.format = Some(template),
+            Err(err) => {
+                eprintln!("perg: {}", err);
+                process::exit(2);
+            }
+        }
+    }
+    let uses_id = config.format.as_ref().is_some_and(|template| template.uses(&Placeholder::Id));
+    // A single PATTERN becomes a one-term expression so matches can be attributed to it
+    let tokens = if has_expression {
+        Some(cli::expression_tokens(&matches))
+    } else if args.pattern_id || args.pattern_stats || uses_id {
+        Some(Ok(vec![Token::Pattern {
+            pattern: config.pattern.clone(),
+            label: None,
+        }]))
+    } else {
+        None
+    };
+    if let Some(tokens) = tokens {
+        match tokens.and_then(Expression::parse) {
+            Ok(expression) => {
+                if args.pattern_stats {
+                    config.pattern_stats = Some(Arc::new(PatternStats::new(&expression)));
+                }
+                config.expression = Some(expression);
+                config.all_match = args.all_match;
+                config.pattern_ids = args.pattern_id;
+            }
+            Err(err) => {
+                eprintln!("perg: {}", err);
+                process::exit(2);
+            }
+        }
+    }
+    config.column = args.column;
+    let proximity = match (args.near, args.not_near) {
+        (Some(lines), _) => Some(Proximity { lines, negate: false }),
+        (None, Some(lines)) => Some(Proximity { lines, negate: true }),
+        (None, None) => None,
+    };
+    if let Some(proximity) = proximity {
+        if config.expression.as_ref().is_none_or(|expression| expression.patterns.len() != 2) {
+            eprintln!("perg: --near and --not-near need exactly two -e patterns");
+            process::exit(2);
+        }
+        config.proximity = Some(proximity);
+    }
+    if args.normalize.is_some() || args.ignore_diacritics {
+        config.normalizer = Some(Normalizer {
+            form: args.normalize.as_deref().and_then(NormalizationForm::from_name),
+            ignore_diacritics: args.ignore_diacritics,
+        });
+    }
+    match args.cidr.iter().map(|spec| Network::parse(spec)).collect() {
+        Ok(networks) => config.networks = networks,
+        Err(err) => {
+            eprintln!("perg: {}", err);
+            process::exit(2);
+        }
+    }
+    // Headings are the default layout on a terminal, unless lines are printed from a template
+    // or the output is structured (JSON Lines, extracted fields, or an aggregate table)
+    let structured_output = config.json || args.extract.is_some() || args.frequency || args.unique;
+    let heading = args.heading
+        || (!args.no_heading && config.format.is_none() && !structured_output && std::io::stdout().is_terminal());
+    if heading {
+        config.headings = Some(Arc::new(Headings::new()));
+    }
+
+    // Capture groups are only available from a plain regex PATTERN
+    if let Some(template) = &config.format {
+        let plain_regex = config.expression.is_none()
+            && config.number_range.is_none()
+            && config.networks.is_empty()
+            && config.fuzzy.is_none();
+        let regex = plain_regex.then(|| regex::Regex::new(&config.pattern));
+        // An invalid PATTERN is reported by the search itself
+        if let Err(err) = regex.map_or(template.check_groups(None), |regex| match regex {
+            Ok(regex) => template.check_groups(Some(&regex)),
+            Err(_) => Ok(()),
+        }) {
+            eprintln!("perg: {}", err);
+            process::exit(2);
+        }
+    }
+
+    if args.frequency || args.unique {
+        if let Some(group) = &args.group {
+            let known = regex::Regex::new(&config.pattern).map_or(true, |regex| {
+                regex.capture_names().flatten().any(|name| name == group)
+                    || group.parse::<usize>().is_ok_and(|number| number < regex.captures_len())
+            });
+            if !known {
+                eprintln!("perg: no capture group {} in pattern", group);
+                process::exit(2);
+            }
+        }
+        let mut frequencies = Frequencies::new(if args.unique { FrequencyMode::Unique } else { FrequencyMode::Counts });
+        frequencies.top = args.top;
+        frequencies.per_file = args.per_file;
+        config.frequencies = Some(Arc::new(frequencies));
+        config.capture_group = args.group;
+    }
+    if let Some(format) = args.extract.as_deref().and_then(ExtractFormat::from_name) {
+        config.extract = Some(Arc::new(Extractor::new(format)));
+    }
 
     // Perform search
     let mut stdout = std::io::stdout();
@@ -52,6 +282,23 @@ fn main() {
         )
     };
 
+    if let Some(frequencies) = &config.frequencies {
+        print!("{}", frequencies.report());
+    }
+    if let Some(stats) = &config.pattern_stats {
+        print!("{}", stats.table());
+    }
+
+    // Errors and warnings from stdin aren't reported by `search_paths`, so report them here
+    if args.paths.is_empty() && !args.no_messages {
+        for warning in config.take_warnings() {
+            eprintln!("perg: (standard input): {}", warning);
+        }
+        if let Err(err) = &result {
+            eprintln!("perg: (standard input): {}", err);
+        }
+    }
+
     match result {
         Ok(_) => process::exit(0),
         Err(PergError::FileNotFound(_)) => process::exit(1),
@@ -0,0 +1,65 @@
+//! Matchers deciding which parts of a record match.
+//!
+//! A regular expression is the default; `--fuzzy` matches the pattern approximately,
+//! and options like `--number-range` and `--cidr` replace it with a matcher that
+//! understands what it is looking for.
+
+use crate::expr::ExpressionMatcher;
+use crate::fuzzy::FuzzyMatcher;
+use crate::network::NetworkMatcher;
+use crate::numeric::NumberMatcher;
+use regex::Regex;
+use std::ops::Range;
+
+/// How records are tested for matches
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    /// The search pattern as a regular expression
+    Regex(Regex),
+    /// Numbers within a range
+    Numbers(NumberMatcher),
+    /// IP addresses inside a set of networks
+    Networks(NetworkMatcher),
+    /// The search pattern as a literal, allowing a number of edits
+    Fuzzy(FuzzyMatcher),
+    /// A boolean expression over several patterns
+    Expression(ExpressionMatcher),
+}
+
+impl Matcher {
+    /// Whether `text` matches, with the byte spans to highlight; a match may have
+    /// no spans, e.g. a line selected by an expression like `--not -e test`
+    pub fn find(&self, text: &str) -> Option<Vec<Range<usize>>> {
+        match self {
+            Matcher::Expression(expression) => expression.find(text),
+            _ => Some(self.find_spans(text)).filter(|spans| !spans.is_empty()),
+        }
+    }
+
+    /// Byte spans of every match in `text`
+    pub fn find_spans(&self, text: &str) -> Vec<Range<usize>> {
+        match self {
+            Matcher::Regex(regex) => regex.find_iter(text).map(|mat| mat.range()).collect(),
+            Matcher::Numbers(numbers) => numbers.find_spans(text),
+            Matcher::Networks(networks) => networks.find_spans(text),
+            Matcher::Fuzzy(fuzzy) => fuzzy.find_spans(text),
+            Matcher::Expression(expression) => expression.find(text).unwrap_or_default(),
+        }
+    }
+
+    /// Edit distance of a match, for matchers that allow edits
+    pub fn distance(&self, matched: &str) -> Option<usize> {
+        match self {
+            Matcher::Fuzzy(fuzzy) => Some(fuzzy.distance(matched)),
+            _ => None,
+        }
+    }
+
+    /// The underlying regex, for modes that match across the whole input
+    pub fn regex(&self) -> Option<&Regex> {
+        match self {
+            Matcher::Regex(regex) => Some(regex),
+            _ => None,
+        }
+    }
+}
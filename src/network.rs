@@ -0,0 +1,152 @@
+//! IP address and CIDR matching.
+//!
+//! Finds IPv4 and IPv6 addresses in text and matches those inside any of a set
+//! of networks, like `grepcidr`. Digits that only look like part of an address,
+//! such as `110.0.0.1` when searching `10.0.0.0/8` or a version string like
+//! `1.2.3.4.5`, are not matched.
+
+use crate::error::{PergError, Result};
+use regex::Regex;
+use std::net::IpAddr;
+use std::ops::Range;
+
+/// An IP network in CIDR notation; a bare address is a single-host network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Network {
+    address: IpAddr,
+    prefix: u8,
+}
+
+impl Network {
+    /// Parse `10.0.0.0/8`, `2001:db8::/32` or a single address
+    pub fn parse(spec: &str) -> Result<Self> {
+        let invalid = || PergError::InvalidPattern(format!("invalid CIDR: {}", spec));
+        let (address, prefix) = match spec.trim().split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (spec.trim(), None),
+        };
+        let address: IpAddr = address.parse().map_err(|_| invalid())?;
+        let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse::<u8>().ok().filter(|&prefix| prefix <= max_prefix).ok_or_else(invalid)?,
+            None => max_prefix,
+        };
+        Ok(Self { address, prefix })
+    }
+
+    /// Whether an address lies inside the network
+    pub fn contains(&self, address: IpAddr) -> bool {
+        match (self.address, address) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix)).unwrap_or(0);
+                u32::from(network) & mask == u32::from(address) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix)).unwrap_or(0);
+                u128::from(network) & mask == u128::from(address) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Finds addresses in a line that belong to any of a set of networks
+#[derive(Debug, Clone)]
+pub struct NetworkMatcher {
+    networks: Vec<Network>,
+    ipv4: Regex,
+    ipv6: Regex,
+}
+
+impl NetworkMatcher {
+    pub fn new(networks: Vec<Network>) -> Self {
+        Self {
+            networks,
+            ipv4: Regex::new(r"\d{1,3}(?:\.\d{1,3}){3}").expect("valid IPv4 regex"),
+            ipv6: Regex::new(r"(?:[0-9A-Fa-f]{0,4}:){2,7}(?:\d{1,3}(?:\.\d{1,3}){3}|[0-9A-Fa-f]{0,4})")
+                .expect("valid IPv6 regex"),
+        }
+    }
+
+    /// Byte spans of the addresses in `text` that fall within a network
+    pub fn find_spans(&self, text: &str) -> Vec<Range<usize>> {
+        let mut addresses: Vec<(Range<usize>, IpAddr)> = self
+            .ipv6
+            .find_iter(text)
+            .chain(self.ipv4.find_iter(text))
+            .filter_map(|mat| self.address(text, mat.range()))
+            .collect();
+
+        // An IPv4 tail of an IPv6 address (`::ffff:10.0.0.1`) is part of the longer match
+        addresses.sort_by_key(|(span, _)| (span.start, std::cmp::Reverse(span.end)));
+        let mut spans: Vec<Range<usize>> = Vec::new();
+        let mut covered = 0;
+        for (span, address) in addresses {
+            if span.start < covered {
+                continue;
+            }
+            covered = span.end;
+            if self.networks.iter().any(|network| network.contains(address)) {
+                spans.push(span);
+            }
+        }
+        spans
+    }
+
+    /// Parse a candidate as a standalone address, dropping a trailing `:` that
+    /// belongs to the surrounding text (`2001:db8::1: refused`)
+    fn address(&self, text: &str, mut span: Range<usize>) -> Option<(Range<usize>, IpAddr)> {
+        let address = loop {
+            match text[span.clone()].parse::<IpAddr>() {
+                Ok(address) => break address,
+                Err(_) if text[span.clone()].ends_with(':') => span.end -= 1,
+                Err(_) => return None,
+            }
+        };
+
+        let before = text[..span.start].chars().next_back();
+        if before.is_some_and(|c| c.is_alphanumeric() || matches!(c, '_' | '.') || (c == ':' && address.is_ipv6())) {
+            return None;
+        }
+        let mut after = text[span.end..].chars();
+        let next = after.next();
+        if next.is_some_and(|c| c.is_alphanumeric() || c == '_')
+            || (next == Some('.') && after.next().is_some_and(|c| c.is_ascii_digit()))
+        {
+            return None;
+        }
+        Some((span, address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(networks: &[&str]) -> NetworkMatcher {
+        NetworkMatcher::new(networks.iter().map(|spec| Network::parse(spec).unwrap()).collect())
+    }
+
+    #[test]
+    fn test_parse_networks() {
+        let network = Network::parse("10.0.0.0/8").unwrap();
+        assert!(network.contains("10.255.0.1".parse().unwrap()));
+        assert!(!network.contains("11.0.0.1".parse().unwrap()));
+        assert!(Network::parse("0.0.0.0/0").unwrap().contains("8.8.8.8".parse().unwrap()));
+        assert!(Network::parse("2001:db8::1").unwrap().contains("2001:db8::1".parse().unwrap()));
+        assert!(Network::parse("10.0.0.0/33").is_err());
+        assert!(Network::parse("10.0.0/8").is_err());
+    }
+
+    #[test]
+    fn test_find_addresses() {
+        let matcher = matcher(&["10.0.0.0/8", "2001:db8::/32"]);
+        let text = "110.0.0.1 10.1.2.3:443 v10.0.0.1 1.10.0.0.1 [2001:db8::7]:80 2001:db9::1 at 10:00:00";
+        let found: Vec<_> = matcher.find_spans(text).into_iter().map(|span| &text[span]).collect();
+        assert_eq!(found, vec!["10.1.2.3", "2001:db8::7"]);
+
+        let text = "peer 2001:db8::1: refused, mapped ::ffff:10.0.0.1";
+        let found: Vec<_> = matcher.find_spans(text).into_iter().map(|span| &text[span]).collect();
+        assert_eq!(found, vec!["2001:db8::1"]);
+    }
+}
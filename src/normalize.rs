@@ -0,0 +1,167 @@
+//! Unicode normalization and diacritic-insensitive matching.
+//!
+//! The pattern and each searched record are normalized before matching, and
+//! match spans are mapped back to byte offsets in the original record so that
+//! `-o`, `--column` and highlighting refer to the text as it appears in the file.
+
+use std::ops::Range;
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+/// Unicode normalization form applied by `--normalize`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalizationForm {
+    /// Canonical composition: `e` + combining acute becomes `é`
+    Nfc,
+    /// Compatibility composition: also folds ligatures, full-width forms and the like
+    Nfkc,
+}
+
+impl NormalizationForm {
+    /// Look up a form by its `--normalize` name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "nfc" => Some(NormalizationForm::Nfc),
+            "nfkc" => Some(NormalizationForm::Nfkc),
+            _ => None,
+        }
+    }
+}
+
+/// Rewrites text into the form the pattern is matched against
+#[derive(Debug, Clone, Default)]
+pub struct Normalizer {
+    pub form: Option<NormalizationForm>,
+    /// Drop accents and other combining marks, and spell out letters like `ß` as `ss`
+    pub ignore_diacritics: bool,
+}
+
+/// Normalized text, with the original byte range each of its bytes came from
+#[derive(Debug)]
+pub struct Normalized {
+    pub text: String,
+    /// `(start, end)` of the original character cluster for each normalized byte;
+    /// `None` when normalization left the text unchanged
+    origins: Option<Vec<(usize, usize)>>,
+}
+
+impl Normalized {
+    /// Map a span of the normalized text back to the original text, widened to
+    /// whole characters with their combining marks
+    pub fn original_span(&self, span: Range<usize>) -> Range<usize> {
+        let Some(origins) = &self.origins else {
+            return span;
+        };
+        let original_len = origins.last().map_or(0, |&(_, end)| end);
+        let start = origins.get(span.start).map_or(original_len, |&(start, _)| start);
+        let end = match span.end.checked_sub(1) {
+            Some(last) if span.end > span.start => origins[last].1,
+            _ => start,
+        };
+        start..end
+    }
+}
+
+impl Normalizer {
+    /// Normalize text, one base character with its combining marks at a time
+    pub fn normalize(&self, text: &str) -> Normalized {
+        if text.is_ascii() {
+            return Normalized {
+                text: text.to_string(),
+                origins: None,
+            };
+        }
+
+        let mut output = String::with_capacity(text.len());
+        let mut origins = Vec::with_capacity(text.len());
+        let mut cluster_start = 0;
+        for (idx, c) in text.char_indices().skip(1) {
+            if !is_combining_mark(c) {
+                self.push_cluster(&text[cluster_start..idx], &mut output);
+                origins.resize(output.len(), (cluster_start, idx));
+                cluster_start = idx;
+            }
+        }
+        self.push_cluster(&text[cluster_start..], &mut output);
+        origins.resize(output.len(), (cluster_start, text.len()));
+
+        Normalized {
+            text: output,
+            origins: Some(origins),
+        }
+    }
+
+    fn push_cluster(&self, cluster: &str, output: &mut String) {
+        if !self.ignore_diacritics {
+            match self.form {
+                Some(NormalizationForm::Nfc) => output.extend(cluster.nfc()),
+                Some(NormalizationForm::Nfkc) => output.extend(cluster.nfkc()),
+                None => output.push_str(cluster),
+            }
+            return;
+        }
+
+        let decomposed: Box<dyn Iterator<Item = char>> = match self.form {
+            Some(NormalizationForm::Nfkc) => Box::new(cluster.nfkd()),
+            _ => Box::new(cluster.nfd()),
+        };
+        for c in decomposed.filter(|&c| !is_combining_mark(c)) {
+            match spell_out(c) {
+                Some(letters) => output.push_str(letters),
+                None => output.push(c),
+            }
+        }
+    }
+}
+
+/// Letters without a Unicode decomposition that are conventionally written without the mark
+fn spell_out(c: char) -> Option<&'static str> {
+    Some(match c {
+        'ß' => "ss",
+        'ẞ' => "SS",
+        'æ' => "ae",
+        'Æ' => "AE",
+        'œ' => "oe",
+        'Œ' => "OE",
+        'ø' => "o",
+        'Ø' => "O",
+        'đ' => "d",
+        'Đ' => "D",
+        'ł' => "l",
+        'Ł' => "L",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maps_spans_to_original_bytes() {
+        let normalizer = Normalizer {
+            form: Some(NormalizationForm::Nfc),
+            ignore_diacritics: false,
+        };
+        // `e` followed by a combining acute accent composes to a single `é`
+        let text = "cafe\u{301} au lait";
+        let normalized = normalizer.normalize(text);
+        assert_eq!(normalized.text, "café au lait");
+        let start = normalized.text.find("café").unwrap();
+        assert_eq!(normalized.original_span(start..start + "café".len()), 0..6);
+    }
+
+    #[test]
+    fn test_ignores_diacritics() {
+        let normalizer = Normalizer {
+            form: None,
+            ignore_diacritics: true,
+        };
+        let text = "Straße, Crème brûlée";
+        let normalized = normalizer.normalize(text);
+        assert_eq!(normalized.text, "Strasse, Creme brulee");
+        // `ss` maps back to the whole `ß`
+        assert_eq!(&text[normalized.original_span(4..6)], "ß");
+        assert_eq!(&text[normalized.original_span(9..14)], "Crème");
+    }
+}
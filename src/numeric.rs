@@ -0,0 +1,204 @@
+//! Numeric range matching.
+//!
+//! Finds numbers in text (integers, floats, negative values and numbers with
+//! thousand separators such as `1,234,567`) and matches those that fall within
+//! a range.
+
+use crate::error::{PergError, Result};
+use regex::Regex;
+use std::ops::Range;
+
+/// One end of a numeric range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bound {
+    Unbounded,
+    Inclusive(f64),
+    Exclusive(f64),
+}
+
+/// Range of numbers accepted by `--number-range`
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumberRange {
+    pub low: Bound,
+    pub high: Bound,
+}
+
+impl NumberRange {
+    /// Parse a range written as a comparison (`>500`, `<=1.5`, `=0`), an interval
+    /// (`[500,1000)`, `(0,]`) or a Rust-style inclusive range (`500..1000`, `500..`, `..-1`)
+    pub fn parse(spec: &str) -> Result<Self> {
+        let invalid = || PergError::InvalidPattern(format!("invalid number range: {}", spec));
+        let number = |text: &str| parse_number(text.trim()).ok_or_else(invalid);
+        let spec = spec.trim();
+
+        let (low, high) = if let Some(rest) = spec.strip_prefix(">=") {
+            (Bound::Inclusive(number(rest)?), Bound::Unbounded)
+        } else if let Some(rest) = spec.strip_prefix("<=") {
+            (Bound::Unbounded, Bound::Inclusive(number(rest)?))
+        } else if let Some(rest) = spec.strip_prefix('>') {
+            (Bound::Exclusive(number(rest)?), Bound::Unbounded)
+        } else if let Some(rest) = spec.strip_prefix('<') {
+            (Bound::Unbounded, Bound::Exclusive(number(rest)?))
+        } else if let Some(rest) = spec.strip_prefix('=') {
+            let value = number(rest)?;
+            (Bound::Inclusive(value), Bound::Inclusive(value))
+        } else if spec.starts_with(['[', '(']) && spec.ends_with([']', ')']) && spec.len() >= 2 {
+            let (low, high) = spec[1..spec.len() - 1].split_once(',').ok_or_else(invalid)?;
+            let bound = |text: &str, inclusive: bool| -> Result<Bound> {
+                Ok(match text.trim() {
+                    "" => Bound::Unbounded,
+                    text if inclusive => Bound::Inclusive(number(text)?),
+                    text => Bound::Exclusive(number(text)?),
+                })
+            };
+            (bound(low, spec.starts_with('['))?, bound(high, spec.ends_with(']'))?)
+        } else if let Some((low, high)) = spec.split_once("..") {
+            let bound = |text: &str| -> Result<Bound> {
+                Ok(match text.trim() {
+                    "" => Bound::Unbounded,
+                    text => Bound::Inclusive(number(text)?),
+                })
+            };
+            (bound(low)?, bound(high)?)
+        } else {
+            return Err(invalid());
+        };
+
+        Ok(Self { low, high })
+    }
+
+    /// Whether a number falls within the range
+    pub fn contains(&self, value: f64) -> bool {
+        let above_low = match self.low {
+            Bound::Unbounded => true,
+            Bound::Inclusive(low) => value >= low,
+            Bound::Exclusive(low) => value > low,
+        };
+        let below_high = match self.high {
+            Bound::Unbounded => true,
+            Bound::Inclusive(high) => value <= high,
+            Bound::Exclusive(high) => value < high,
+        };
+        above_low && below_high
+    }
+}
+
+/// Parse a number, accepting thousand separators
+fn parse_number(text: &str) -> Option<f64> {
+    text.replace(',', "").parse::<f64>().ok().filter(|value| value.is_finite())
+}
+
+/// Finds numbers in a line that fall within a range, optionally only right after a key
+#[derive(Debug, Clone)]
+pub struct NumberMatcher {
+    range: NumberRange,
+    key: Option<String>,
+    number: Regex,
+}
+
+impl NumberMatcher {
+    pub fn new(range: NumberRange, key: Option<String>) -> Self {
+        Self {
+            range,
+            key,
+            number: Regex::new(r"-?(?:\d{1,3}(?:,\d{3})+|\d+)(?:\.\d+)?").expect("valid number regex"),
+        }
+    }
+
+    /// Byte spans of the numbers in `text` that fall within the range
+    pub fn find_spans(&self, text: &str) -> Vec<Range<usize>> {
+        let candidates = match &self.key {
+            Some(key) => text
+                .match_indices(key.as_str())
+                .filter_map(|(idx, _)| self.number_after_key(text, idx + key.len()))
+                .collect(),
+            None => self.numbers(text),
+        };
+
+        candidates
+            .into_iter()
+            .filter(|span| parse_number(&text[span.clone()]).is_some_and(|value| self.range.contains(value)))
+            .collect()
+    }
+
+    /// Every standalone number in the text
+    fn numbers(&self, text: &str) -> Vec<Range<usize>> {
+        self.number
+            .find_iter(text)
+            .filter_map(|mat| self.standalone(text, mat.range()))
+            .collect()
+    }
+
+    /// The number directly following a key, skipping quotes, separators and whitespace
+    fn number_after_key(&self, text: &str, after_key: usize) -> Option<Range<usize>> {
+        let rest = &text[after_key..];
+        let skipped = rest.len() - rest.trim_start_matches(|c: char| c.is_whitespace() || "\"':=".contains(c)).len();
+        let start = after_key + skipped;
+        let mat = self.number.find_at(text, start).filter(|mat| mat.start() == start)?;
+        self.standalone(text, mat.range())
+    }
+
+    /// Reject digits that are part of a word or a version string like `v2` or `1.2.3`;
+    /// a `-` glued to a preceding word (`5-10`) is a dash rather than a sign. A comma
+    /// that would continue a thousands group is already part of the match, so one
+    /// after it separates values, as in `10,20`.
+    fn standalone(&self, text: &str, mut span: Range<usize>) -> Option<Range<usize>> {
+        let before = text[..span.start].chars().next_back();
+        if text[span.clone()].starts_with('-') && before.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            span.start += 1;
+        } else if before.is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '.') {
+            return None;
+        }
+
+        let mut after = text[span.end..].chars();
+        if after.next() == Some('.') && after.next().is_some_and(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        Some(span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ranges() {
+        let range = NumberRange::parse("(500,1000]").unwrap();
+        assert!(!range.contains(500.0) && range.contains(500.5) && range.contains(1000.0));
+
+        let range = NumberRange::parse(">=1,000").unwrap();
+        assert!(range.contains(1000.0) && !range.contains(999.9));
+
+        let range = NumberRange::parse("..-1").unwrap();
+        assert!(range.contains(-1.0) && !range.contains(0.0));
+
+        assert!(NumberRange::parse("500").is_err());
+        assert!(NumberRange::parse("[a,b]").is_err());
+    }
+
+    #[test]
+    fn test_find_numbers() {
+        let matcher = NumberMatcher::new(NumberRange::parse(">500").unwrap(), None);
+        let text = "v2 took 1,250.5ms, retries=-3 from 1.2.3.4 range 5-900 http2";
+        let found: Vec<_> = matcher.find_spans(text).into_iter().map(|span| &text[span]).collect();
+        assert_eq!(found, vec!["1,250.5", "900"]);
+
+        let matcher = NumberMatcher::new(NumberRange::parse("<0").unwrap(), None);
+        let found: Vec<_> = matcher.find_spans(text).into_iter().map(|span| &text[span]).collect();
+        assert_eq!(found, vec!["-3"]);
+
+        let matcher = NumberMatcher::new(NumberRange::parse("[5,20]").unwrap(), None);
+        let text = "list=10,20 ids=1234,7";
+        let found: Vec<_> = matcher.find_spans(text).into_iter().map(|span| &text[span]).collect();
+        assert_eq!(found, vec!["10", "20", "7"]);
+    }
+
+    #[test]
+    fn test_find_numbers_after_key() {
+        let matcher = NumberMatcher::new(NumberRange::parse(">500").unwrap(), Some("latency_ms".to_string()));
+        let text = r#"{"size": 900, "latency_ms": 612}"#;
+        let found: Vec<_> = matcher.find_spans(text).into_iter().map(|span| &text[span]).collect();
+        assert_eq!(found, vec!["612"]);
+    }
+}
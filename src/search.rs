@@ -1,31 +1,167 @@
+use crate::color::{Color, ColorScheme, Style};
 use crate::error::{PergError, Result};
-use console::style;
-use regex::Regex;
+use crate::expr::{Expression, ExpressionMatcher, PatternStats};
+use crate::extract::Extractor;
+use crate::frequency::Frequencies;
+use crate::fuzzy::FuzzyMatcher;
+use crate::hyperlink::{self, HyperlinkFormat};
+use crate::matcher::Matcher;
+use crate::network::{Network, NetworkMatcher};
+use crate::normalize::Normalizer;
+use crate::numeric::{NumberMatcher, NumberRange};
+use crate::structured::{self, KeyPath, StructuredFormat};
+use crate::template::{Fields, Placeholder, Template};
+use crate::tabular::{self, CsvOptions};
+use crate::timestamp::TimeFilter;
+use regex::{Captures, Match, Regex, RegexBuilder};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs::File;
-use std::io::{BufRead, BufReader, Write, stdin};
+use std::io::{BufRead, BufReader, Read, Seek, Write, stdin};
+use std::ops::{Range, RangeInclusive};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use walkdir::WalkDir;
 
+/// Largest input, in bytes, that multiline and record modes will load into memory
+pub const MAX_BUFFERED_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Record separator used by `--paragraph`: one or more blank lines
+pub const PARAGRAPH_SEPARATOR: &str = r"\n[ \t\r]*\n(?:[ \t\r]*\n)*";
+
 /// Helper function to determine if we should use colors
 fn use_colors(color_option: &str) -> bool {
     match color_option {
         "always" => true,
         "never" => false,
-        "auto" => console::colors_enabled(),
         _ => console::colors_enabled(),
     }
 }
 
-/// Helper function to colorize matches in a line
-fn colorize_matches(line: &str, regex: &Regex, color_option: &str) -> String {
-    if !use_colors(color_option) {
+/// Color styles to apply to the output, if it is colored
+fn colors(config: &SearchConfig) -> Option<&ColorScheme> {
+    Some(&config.colors).filter(|_| use_colors(&config.color))
+}
+
+/// Largest character boundary in `text` at or before `idx`
+fn floor_boundary(text: &str, idx: usize) -> usize {
+    let mut idx = idx.min(text.len());
+    while !text.is_char_boundary(idx) {
+        idx -= 1;
+    }
+    idx
+}
+
+/// Smallest character boundary in `text` at or after `idx`
+fn ceil_boundary(text: &str, idx: usize) -> usize {
+    let mut idx = idx.min(text.len());
+    while !text.is_char_boundary(idx) {
+        idx += 1;
+    }
+    idx
+}
+
+/// At most `width` bytes of `line` around `focus`, starting and ending on character boundaries
+fn preview_window(line: &str, width: usize, focus: Range<usize>) -> Range<usize> {
+    let margin = width.saturating_sub(focus.len()) / 2;
+    let mut start = floor_boundary(line, focus.start.saturating_sub(margin));
+    if line.len() - start < width {
+        // Near the end of the line, use the whole width before it
+        start = ceil_boundary(line, line.len().saturating_sub(width));
+    }
+    start..floor_boundary(line, start + width)
+}
+
+/// A matching line as printed: highlighted, and omitted or shortened when longer than
+/// `--max-columns`; `highlights` are sorted by start
+fn display_line(config: &SearchConfig, line: &str, highlights: &[(Range<usize>, usize)], matches: usize) -> String {
+    let Some(width) = config.max_columns.filter(|&width| line.len() > width) else {
+        return colorize_matches(line, highlights, colors(config));
+    };
+    if !config.max_columns_preview {
+        // Inverted matches have nothing to count
+        if matches == 0 {
+            return "[omitted long line]".to_string();
+        }
+        return format!("[omitted long line with {} matches]", matches);
+    }
+
+    let focus = highlights.first().map_or(0..0, |(span, _)| span.clone());
+    let window = preview_window(line, width, focus);
+    // Highlights move with the window and are cut at its ends
+    let clipped: Vec<_> = highlights
+        .iter()
+        .filter_map(|(span, pattern)| {
+            let start = span.start.max(window.start);
+            let end = span.end.min(window.end);
+            (start < end).then(|| (start - window.start..end - window.start, *pattern))
+        })
+        .collect();
+    let mut preview = colorize_matches(&line[window.clone()], &clipped, colors(config));
+    if window.start > 0 {
+        preview.insert(0, '…');
+    }
+    if window.end < line.len() {
+        preview.push('…');
+    }
+    preview
+}
+
+/// A context line as printed: styled, and omitted or cut short when longer than `--max-columns`
+fn display_context_line(config: &SearchConfig, line: &str) -> String {
+    let (text, cut) = match config.max_columns.filter(|&width| line.len() > width) {
+        Some(_) if !config.max_columns_preview => return "[omitted long context line]".to_string(),
+        Some(width) => (&line[..floor_boundary(line, width)], true),
+        None => (line, false),
+    };
+    let mut display = match colors(config) {
+        Some(colors) => colors.context.paint(text),
+        None => text.to_string(),
+    };
+    if cut {
+        display.push('…');
+    }
+    display
+}
+
+/// URL for a hyperlink to a file or a position in it; links are only added to
+/// colored output, which is meant for a terminal
+fn hyperlink_url(config: &SearchConfig, path: Option<&str>, line: Option<usize>, column: Option<usize>) -> Option<String> {
+    let format = config.hyperlinks.as_ref().filter(|_| colors(config).is_some())?;
+    format.url(path?, line, column)
+}
+
+/// Highlight colors for the matches of each pattern when several are searched for, in turn;
+/// the first pattern uses the match style
+const PATTERN_COLORS: [Color; 6] = [Color::RED, Color::GREEN, Color::YELLOW, Color::BLUE, Color::MAGENTA, Color::CYAN];
+
+/// Helper function to colorize matches in a line, each in the color of the pattern
+/// (by index) that matched it, and the rest in the selected line style; spans must be
+/// sorted by start
+fn colorize_matches(line: &str, spans: &[(Range<usize>, usize)], colors: Option<&ColorScheme>) -> String {
+    let Some(colors) = colors else {
         return line.to_string();
+    };
+
+    let mut output = String::with_capacity(line.len());
+    let mut last = 0;
+    for (span, pattern) in spans {
+        // Spans from several patterns may overlap; color each byte once
+        let start = span.start.max(last);
+        if start >= span.end {
+            continue;
+        }
+        output.push_str(&colors.selected.paint(&line[last..start]));
+        let text = &line[start..span.end];
+        output.push_str(&match pattern % PATTERN_COLORS.len() {
+            0 => colors.matched.paint(text),
+            idx => Style::bold(PATTERN_COLORS[idx]).paint(text),
+        });
+        last = span.end;
     }
-    
-    // Use the regex to find all matches and replace them with colored versions
-    regex.replace_all(line, |caps: &regex::Captures| {
-        style(&caps[0]).red().bold().to_string()
-    }).to_string()
+    output.push_str(&colors.selected.paint(&line[last..]));
+    output
 }
 
 /// Search configuration
@@ -46,9 +182,95 @@ pub struct SearchConfig {
     pub only_matching: bool,
     pub extended_regexp: bool,
     pub color: String,
+    /// Run the pattern over the whole input so matches can span lines
+    pub multiline: bool,
+    /// In multiline mode, let `.` match newlines as well
+    pub multiline_dotall: bool,
+    /// Treat input and output records as NUL-terminated instead of newline-terminated
+    pub null_data: bool,
+    /// Print a NUL byte after each filename instead of the usual separator
+    pub null: bool,
+    /// Regex separating input records, replacing lines as the unit of matching
+    pub record_separator: Option<String>,
+    /// Regex marking the first line of a record; other lines join the record before them
+    pub record_start: Option<String>,
+    /// Only consider lines whose timestamp falls inside this window
+    pub time_filter: Option<TimeFilter>,
+    /// Read input as CSV/TSV records and test the pattern against one column
+    pub csv: Option<CsvOptions>,
+    /// Read input as JSON documents and test the pattern against the values at this path
+    pub json_path: Option<KeyPath>,
+    /// Pretty-print matching JSON documents
+    pub json_pretty: bool,
+    /// Read input as YAML or TOML and test the pattern against the values at this path
+    pub key_path: Option<KeyPath>,
+    /// Format for `key_path` searches; detected from the file extension when unset
+    pub input_format: Option<StructuredFormat>,
+    /// Match numbers within this range instead of the pattern
+    pub number_range: Option<NumberRange>,
+    /// Only consider numbers directly following this key, e.g. `latency_ms=`
+    pub number_key: Option<String>,
+    /// Match IP addresses inside any of these networks instead of the pattern
+    pub networks: Vec<Network>,
+    /// Match the pattern as a literal within this many insertions, deletions or substitutions
+    pub fuzzy: Option<usize>,
+    /// Print one JSON object per output line, with the spans of each match
+    pub json: bool,
+    /// Normalize the pattern and each record before matching
+    pub normalizer: Option<Normalizer>,
+    /// Show the 1-based byte column of the first match after the line number
+    pub column: bool,
+    /// Boolean expression over several patterns, used instead of `pattern`
+    pub expression: Option<Expression>,
+    /// Only report inputs in which every pattern of the expression matches somewhere
+    pub all_match: bool,
+    /// Match the first `-e` pattern only near (or, negated, away from) the second
+    pub proximity: Option<Proximity>,
+    /// Prefix output lines with the labels of the patterns they matched
+    pub pattern_ids: bool,
+    /// Matching line counts per pattern, shared by every searched input
+    pub pattern_stats: Option<Arc<PatternStats>>,
+    /// Tally match texts across all inputs instead of printing lines
+    pub frequencies: Option<Arc<Frequencies>>,
+    /// Capture group (number or name) whose text is used instead of the whole match
+    pub capture_group: Option<String>,
+    /// Print the capture groups of each match as table rows instead of lines
+    pub extract: Option<Arc<Extractor>>,
+    /// Template for output lines instead of the default `path:line:content` layout
+    pub format: Option<Template>,
+    /// Print each file's path once above its lines instead of on every line
+    pub headings: Option<Arc<Headings>>,
+    /// Styles for each part of colored output
+    pub colors: ColorScheme,
+    /// Link paths and line numbers in colored output with URLs in this format
+    pub hyperlinks: Option<HyperlinkFormat>,
+    /// Print every record, the ones that don't match as context
+    pub passthru: bool,
+    /// Lines longer than this many bytes are omitted or, with `max_columns_preview`, shortened
+    pub max_columns: Option<usize>,
+    /// Show part of over-long lines around their first match instead of omitting them
+    pub max_columns_preview: bool,
+    /// Line printed between non-contiguous groups of context output; `None` prints nothing
+    pub group_separator: Option<Arc<GroupSeparator>>,
+    /// Separator after the path, line and column of matching lines
+    pub match_separator: String,
+    /// Separator after the path and line number of context lines
+    pub context_separator: String,
+    /// Problems that didn't stop the search of an input, for the caller to report
+    pub warnings: Arc<Mutex<Vec<PergError>>>,
+}
+
+/// Proximity between the two `-e` patterns required by `--near`/`--not-near`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Proximity {
+    /// Largest distance in lines between the two matches
+    pub lines: usize,
+    /// Select lines of the first pattern with no line of the second within `lines`
+    pub negate: bool,
 }
 
 impl SearchConfig {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         pattern: String,
         ignore_case: bool,
@@ -82,8 +304,114 @@ impl SearchConfig {
             only_matching,
             extended_regexp,
             color,
+            multiline: false,
+            multiline_dotall: false,
+            null_data: false,
+            null: false,
+            record_separator: None,
+            record_start: None,
+            time_filter: None,
+            csv: None,
+            json_path: None,
+            json_pretty: false,
+            key_path: None,
+            input_format: None,
+            number_range: None,
+            number_key: None,
+            networks: Vec::new(),
+            fuzzy: None,
+            json: false,
+            normalizer: None,
+            column: false,
+            expression: None,
+            all_match: false,
+            proximity: None,
+            pattern_ids: false,
+            pattern_stats: None,
+            frequencies: None,
+            capture_group: None,
+            extract: None,
+            format: None,
+            headings: None,
+            colors: ColorScheme::default(),
+            hyperlinks: None,
+            passthru: false,
+            max_columns: None,
+            max_columns_preview: false,
+            group_separator: Some(Arc::new(GroupSeparator::new("--"))),
+            match_separator: ":".to_string(),
+            context_separator: "-".to_string(),
+            warnings: Arc::new(Mutex::new(Vec::new())),
         }
     }
+
+    /// Take the warnings recorded since the last call
+    pub fn take_warnings(&self) -> Vec<PergError> {
+        std::mem::take(&mut *self.warnings.lock().expect("warnings lock"))
+    }
+}
+
+/// Separator line between groups of context output that aren't adjacent, within a
+/// file and between files
+#[derive(Debug)]
+pub struct GroupSeparator {
+    text: String,
+    started: AtomicBool,
+}
+
+impl GroupSeparator {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            started: AtomicBool::new(false),
+        }
+    }
+
+    /// Write the separator before a group of lines, unless it is the first group of the
+    /// output or `skip` is set
+    fn write(&self, config: &SearchConfig, writer: &mut impl Write, skip: bool) -> Result<()> {
+        if !self.started.swap(true, Ordering::Relaxed) || skip {
+            return Ok(());
+        }
+        match colors(config) {
+            Some(colors) => write_record(config, writer, &colors.separator.paint(&self.text)),
+            None => write_record(config, writer, &self.text),
+        }
+    }
+}
+
+/// Heading layout: each file's path on a line of its own above its numbered lines,
+/// with a blank line between files
+#[derive(Debug, Default)]
+pub struct Headings {
+    printed: AtomicBool,
+}
+
+impl Headings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write the heading for a file, after a blank line unless it is the first;
+    /// with `-Z` the path ends in NUL instead of the record terminator
+    fn write(&self, config: &SearchConfig, writer: &mut impl Write, path: &str) -> Result<()> {
+        if self.printed.swap(true, Ordering::Relaxed) {
+            write_record(config, writer, "")?;
+        }
+        let mut heading = match colors(config) {
+            Some(colors) => colors.path.paint(path),
+            None => path.to_string(),
+        };
+        if let Some(url) = hyperlink_url(config, Some(path), None, None) {
+            heading = hyperlink::link(&url, &heading);
+        }
+        if config.null {
+            writer.write_all(heading.as_bytes())?;
+            writer.write_all(b"\0")?;
+            return Ok(());
+        }
+        write_record(config, writer, &heading)
+    }
 }
 
 /// Search result for a single match
@@ -94,98 +422,568 @@ pub struct MatchResult {
     pub line_content: String,
 }
 
-/// Search for pattern in a single file
-pub fn search_file(
+/// A unit of input tested by the matcher: a line, or a group of lines in record modes
+#[derive(Debug, Clone)]
+pub(crate) struct Record {
+    /// Line number where the record starts (1-based)
+    pub(crate) line_number: usize,
+    pub(crate) text: String,
+    /// Field tested instead of the whole text, e.g. a CSV column
+    pub(crate) field: Option<Field>,
+    /// Byte offset of the record in the input, when it appears there verbatim
+    pub(crate) byte_offset: Option<usize>,
+}
+
+/// Part of a record that the pattern is tested against
+#[derive(Debug, Clone)]
+pub(crate) struct Field {
+    pub(crate) value: String,
+    /// Where the value appears verbatim in the record text, used for highlighting
+    pub(crate) offset: Option<usize>,
+}
+
+impl Record {
+    fn new(line_number: usize, text: String) -> Self {
+        Self { line_number, text, field: None, byte_offset: None }
+    }
+
+    /// Text the pattern is tested against
+    fn subject(&self) -> &str {
+        self.field.as_ref().map_or(&self.text, |field| &field.value)
+    }
+
+    /// Where the subject starts within the record text, if it appears there verbatim
+    fn subject_offset(&self) -> Option<usize> {
+        self.field.as_ref().map_or(Some(0), |field| field.offset)
+    }
+}
+
+/// Selected line indices mapped to the byte spans that matched within each line
+type Selection = BTreeMap<usize, Vec<Range<usize>>>;
+
+/// Byte that terminates input and output records
+fn record_terminator(config: &SearchConfig) -> u8 {
+    if config.null_data { b'\0' } else { b'\n' }
+}
+
+/// Write one output record followed by the configured terminator
+fn write_record(config: &SearchConfig, writer: &mut impl Write, record: &str) -> Result<()> {
+    writer.write_all(record.as_bytes())?;
+    writer.write_all(&[record_terminator(config)])?;
+    Ok(())
+}
+
+/// Write a filename as printed by `-l`/`-L`, NUL-terminated when `-Z` is set
+fn write_filename(config: &SearchConfig, writer: &mut impl Write, path: &str) -> Result<()> {
+    let linked = hyperlink_url(config, Some(path), None, None).map(|url| hyperlink::link(&url, path));
+    let path = linked.as_deref().unwrap_or(path);
+    if config.null {
+        writer.write_all(path.as_bytes())?;
+        writer.write_all(b"\0")?;
+        Ok(())
+    } else {
+        write_record(config, writer, path)
+    }
+}
+
+/// Separator printed after a filename prefix, replaced by NUL when `-Z` is set
+fn filename_separator(config: &SearchConfig, separator: char) -> char {
+    if config.null { '\0' } else { separator }
+}
+
+/// Whether the text the pattern is tested against can contain several lines,
+/// in which case `^` and `$` anchor at line boundaries
+fn spans_lines(config: &SearchConfig) -> bool {
+    config.multiline
+        || config.record_separator.is_some()
+        || config.record_start.is_some()
+        || config.json_path.is_some()
+}
+
+/// Compile the search pattern according to the configuration
+fn build_regex(config: &SearchConfig, pattern: &str) -> Result<Regex> {
+    let regex = RegexBuilder::new(&search_pattern(config, pattern))
+        .case_insensitive(config.ignore_case)
+        .multi_line(spans_lines(config))
+        .dot_matches_new_line(config.multiline && config.multiline_dotall)
+        .build()?;
+    Ok(regex)
+}
+
+/// A pattern as matched, normalized like the records it is tested against
+fn search_pattern<'a>(config: &SearchConfig, pattern: &'a str) -> Cow<'a, str> {
+    match &config.normalizer {
+        Some(normalizer) => Cow::Owned(normalizer.normalize(pattern).text),
+        None => Cow::Borrowed(pattern),
+    }
+}
+
+/// Build the matcher selected by the configuration
+fn build_matcher(config: &SearchConfig) -> Result<Matcher> {
+    if let Some(range) = &config.number_range {
+        return Ok(Matcher::Numbers(NumberMatcher::new(range.clone(), config.number_key.clone())));
+    }
+    if !config.networks.is_empty() {
+        return Ok(Matcher::Networks(NetworkMatcher::new(config.networks.clone())));
+    }
+    if let Some(max_distance) = config.fuzzy {
+        return Ok(Matcher::Fuzzy(FuzzyMatcher::new(&search_pattern(config, &config.pattern), max_distance, config.ignore_case)?));
+    }
+    if let Some(expression) = &config.expression {
+        let terms = expression
+            .patterns
+            .iter()
+            .map(|pattern| build_regex(config, pattern))
+            .collect::<Result<Vec<_>>>()?;
+        return Ok(Matcher::Expression(ExpressionMatcher::new(expression, terms)));
+    }
+    Ok(Matcher::Regex(build_regex(config, &config.pattern)?))
+}
+
+/// Load the whole input into memory, refusing inputs over `MAX_BUFFERED_BYTES`
+fn read_buffer(reader: impl BufRead, name: &str) -> Result<String> {
+    let mut bytes = Vec::new();
+    reader.take(MAX_BUFFERED_BYTES + 1).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > MAX_BUFFERED_BYTES {
+        return Err(PergError::InputTooLarge(name.to_string()));
+    }
+    let buffer = String::from_utf8(bytes)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+    Ok(buffer)
+}
+
+/// Split a buffer into records at each match of the separator regex
+fn split_records(buffer: &str, separator: &Regex) -> Vec<Record> {
+    let mut records = Vec::new();
+    let mut line_number = 1;
+    let mut last = 0;
+    let ends = separator
+        .find_iter(buffer)
+        .filter(|mat| !mat.is_empty())
+        .map(|mat| (mat.start(), mat.end()))
+        .chain(std::iter::once((buffer.len(), buffer.len())));
+
+    for (start, end) in ends {
+        let raw = &buffer[last..start];
+        // Leading newlines are not part of the record but still count towards its line number
+        let text = raw.trim_start_matches(['\r', '\n']);
+        let skipped = raw[..raw.len() - text.len()].matches('\n').count();
+        let offset = last + raw.len() - text.len();
+        let text = text.trim_end_matches(['\r', '\n']);
+        if !text.is_empty() {
+            records.push(Record {
+                byte_offset: Some(offset),
+                ..Record::new(line_number + skipped, text.to_string())
+            });
+        }
+        line_number += buffer[last..end].matches('\n').count();
+        last = end;
+    }
+    records
+}
+
+/// Fold lines that don't match the start regex into the record before them
+fn group_records(lines: Vec<Record>, start: &Regex) -> Vec<Record> {
+    let mut records: Vec<Record> = Vec::new();
+    for line in lines {
+        match records.last_mut() {
+            Some(record) if !start.is_match(&line.text) => {
+                record.text.push('\n');
+                record.text.push_str(&line.text);
+            }
+            _ => records.push(line),
+        }
+    }
+    records
+}
+
+/// Keep only records inside the time window; records without a timestamp take the one before them
+fn filter_time_range(records: Vec<Record>, filter: &TimeFilter) -> Vec<Record> {
+    let mut current = None;
+    records
+        .into_iter()
+        .filter(|record| {
+            let first_line = record.text.lines().next().unwrap_or_default();
+            if let Some(timestamp) = filter.timestamp(first_line) {
+                current = Some(timestamp);
+            }
+            current.is_some_and(|timestamp| filter.contains(timestamp))
+        })
+        .collect()
+}
+
+/// Split a buffer into records at `terminator` with the byte offset of each,
+/// dropping the `\r` of `\r\n` line endings like `str::lines`
+fn split_lines(buffer: &str, terminator: char) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in buffer.split_inclusive(terminator) {
+        let text = match line.strip_suffix(terminator) {
+            Some(text) if terminator == '\n' => text.strip_suffix('\r').unwrap_or(text),
+            Some(text) => text,
+            None => line,
+        };
+        lines.push((offset, text.to_string()));
+        offset += line.len();
+    }
+    lines
+}
+
+/// Read records ending in `terminator` with the byte offset of each, like [`split_lines`]
+fn read_lines(mut reader: impl BufRead, terminator: u8) -> std::io::Result<Vec<(usize, String)>> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    let mut line = Vec::new();
+    loop {
+        let read = reader.read_until(terminator, &mut line)?;
+        if read == 0 {
+            return Ok(lines);
+        }
+        if line.last() == Some(&terminator) {
+            line.pop();
+            if terminator == b'\n' && line.last() == Some(&b'\r') {
+                line.pop();
+            }
+        }
+        let text = String::from_utf8(std::mem::take(&mut line))
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        lines.push((offset, text));
+        offset += read;
+    }
+}
+
+/// Read the input into records, loading it whole when multiline or record matching is enabled
+fn read_input(
     config: &SearchConfig,
-    file_path: &str,
-    writer: &mut impl Write,
-) -> Result<bool> {
-    let path = Path::new(file_path);
+    reader: impl BufRead,
+    name: &str,
+) -> Result<(Vec<Record>, Option<String>)> {
+    let numbered = |lines: Vec<(usize, String)>| -> Vec<Record> {
+        lines
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (offset, text))| Record {
+                byte_offset: Some(offset),
+                ..Record::new(idx + 1, text)
+            })
+            .collect()
+    };
 
-    // Handle directory case for files_with_matches/files_without_match
-    if path.is_dir() && (config.files_with_matches || config.files_without_match) {
-        // For directories in these modes, we consider them as having no matches
-        // since directories themselves don't contain searchable text
-        if config.files_without_match {
-            writeln!(writer, "{}", file_path)?;
-            return Ok(false);
+    if let Some(options) = &config.csv {
+        return Ok((tabular::read_records(reader, options)?, None));
+    }
+
+    if let Some(path) = &config.json_path {
+        let buffer = read_buffer(reader, name)?;
+        let (records, warnings) = structured::read_json_records(&buffer, path, config.json_pretty)?;
+        config.warnings.lock().expect("warnings lock").extend(warnings);
+        return Ok((records, None));
+    }
+
+    if let Some(path) = &config.key_path {
+        let buffer = read_buffer(reader, name)?;
+        let format = config.input_format.unwrap_or_else(|| StructuredFormat::detect(name));
+        match structured::read_document_records(&buffer, path, format) {
+            Ok(records) => return Ok((records, None)),
+            Err(err) => {
+                // Fall back to plain text search so unparseable files aren't silently skipped
+                let warning = match err {
+                    PergError::Parse(message) => PergError::Parse(format!("{}; searching as plain text", message)),
+                    err => err,
+                };
+                config.warnings.lock().expect("warnings lock").push(warning);
+                return Ok((numbered(split_lines(&buffer, '\n')), None));
+            }
         }
-        return Ok(false);
     }
 
-    let file = File::open(path).map_err(|_| PergError::FileNotFound(file_path.to_string()))?;
-    let reader = BufReader::new(file);
+    if let Some(separator) = &config.record_separator {
+        let separator = RegexBuilder::new(separator).multi_line(true).build()?;
+        let buffer = read_buffer(reader, name)?;
+        return Ok((split_records(&buffer, &separator), None));
+    }
 
-    let pattern = if config.ignore_case {
-        format!("(?i){}", config.pattern)
-    } else {
-        config.pattern.clone()
+    if config.multiline {
+        let buffer = read_buffer(reader, name)?;
+        let lines = split_lines(&buffer, char::from(record_terminator(config)));
+        return Ok((numbered(lines), Some(buffer)));
+    }
+
+    let lines = read_lines(reader, record_terminator(config))?;
+    if let Some(start) = &config.record_start {
+        return Ok((group_records(numbered(lines), &Regex::new(start)?), None));
+    }
+    Ok((numbered(lines), None))
+}
+
+/// Test a record's subject, mapping match spans back from its normalized form if needed
+fn find_matches(config: &SearchConfig, matcher: &Matcher, subject: &str) -> Option<Vec<Range<usize>>> {
+    let Some(normalizer) = &config.normalizer else {
+        return matcher.find(subject);
     };
+    let normalized = normalizer.normalize(subject);
+    let spans = matcher.find(&normalized.text)?;
+    Some(spans.into_iter().map(|span| normalized.original_span(span)).collect())
+}
 
-    let regex = Regex::new(&pattern)?;
+/// Edit distance of a match, measured between the normalized forms
+fn match_distance(config: &SearchConfig, matcher: &Matcher, matched: &str) -> Option<usize> {
+    match &config.normalizer {
+        Some(normalizer) => matcher.distance(&normalizer.normalize(matched).text),
+        None => matcher.distance(matched),
+    }
+}
 
-    let mut has_matches = false;
-    let mut match_count = 0;
-    let mut _line_number = 0;
-    let mut lines: Vec<String> = Vec::new();
-    let mut matching_line_indices = Vec::new();
-    
-    // Read all lines to enable context functionality
-    for line in reader.lines() {
-        lines.push(line?);
-        _line_number += 1;
+/// Whether every pattern outside a `--not` matches somewhere in the records, for `--all-match`
+fn matches_all_patterns(config: &SearchConfig, expression: &ExpressionMatcher, records: &[Record]) -> bool {
+    let mut remaining = expression.positive_terms();
+    for record in records {
+        if remaining.is_empty() {
+            break;
+        }
+        let subject = match &config.normalizer {
+            Some(normalizer) => Cow::Owned(normalizer.normalize(record.subject()).text),
+            None => Cow::Borrowed(record.subject()),
+        };
+        remaining.retain(|&term| !expression.term_matches(term, &subject));
     }
+    remaining.is_empty()
+}
 
-    // Use context: if -C is specified, it overrides -A and -B
-    let before_context = if config.context > 0 { config.context } else { config.before_context };
-    let after_context = if config.context > 0 { config.context } else { config.after_context };
+/// Spans of each pattern of an expression in a record's subject, mapped back from its
+/// normalized form if needed
+fn term_spans(config: &SearchConfig, expression: &ExpressionMatcher, subject: &str) -> Vec<Vec<Range<usize>>> {
+    let normalized = config.normalizer.as_ref().map(|normalizer| normalizer.normalize(subject));
+    let text = normalized.as_ref().map_or(subject, |normalized| &normalized.text);
+    (0..expression.term_count())
+        .map(|term| {
+            let spans = expression.term_spans(term, text);
+            match &normalized {
+                Some(normalized) => spans.into_iter().map(|span| normalized.original_span(span)).collect(),
+                None => spans,
+            }
+        })
+        .collect()
+}
 
-    // First pass: find all matching lines
-    for (idx, line) in lines.iter().enumerate() {
-        let matches = regex.is_match(line);
+/// Select lines of the first pattern within (or, negated, not within) `proximity.lines`
+/// lines of the second. Returns the selection and, for `--near`, the windows spanning each
+/// line of the first pattern and its nearby lines of the second.
+fn select_near(
+    config: &SearchConfig,
+    expression: &ExpressionMatcher,
+    records: &[Record],
+    proximity: Proximity,
+) -> (Selection, Vec<RangeInclusive<usize>>) {
+    let mut hits: [Selection; 2] = Default::default();
+    for (idx, record) in records.iter().enumerate() {
+        for (term_hits, spans) in hits.iter_mut().zip(term_spans(config, expression, record.subject())) {
+            if !spans.is_empty() {
+                term_hits.insert(idx, spans);
+            }
+        }
+    }
+    let [first, second] = hits;
+
+    let mut selection = Selection::new();
+    let mut windows = Vec::new();
+    for (&idx, spans) in &first {
+        let nearby = second.range(idx.saturating_sub(proximity.lines)..=idx + proximity.lines);
+        if proximity.negate {
+            if nearby.clone().next().is_none() {
+                selection.insert(idx, spans.clone());
+            }
+            continue;
+        }
+
+        let mut window = idx..=idx;
+        for (&other, other_spans) in nearby {
+            window = (*window.start()).min(other)..=(*window.end()).max(other);
+            selection.entry(other).or_default().extend(other_spans.iter().cloned());
+        }
+        if window != (idx..=idx) || second.contains_key(&idx) {
+            selection.entry(idx).or_default().extend(spans.iter().cloned());
+            windows.push(window);
+        }
+    }
+    for spans in selection.values_mut() {
+        spans.sort_by_key(|span| (span.start, span.end));
+        spans.dedup();
+    }
+    (selection, windows)
+}
+
+/// Test each record on its own against the pattern
+fn select_records(config: &SearchConfig, matcher: &Matcher, records: &[Record]) -> Selection {
+    let mut selection = Selection::new();
+    for (idx, record) in records.iter().enumerate() {
+        let spans = find_matches(config, matcher, record.subject());
 
         // Apply invert match logic
-        let should_include = if config.invert_match { !matches } else { matches };
+        if spans.is_some() != config.invert_match {
+            selection.insert(idx, spans.unwrap_or_default());
+        }
+    }
+    selection
+}
 
-        if should_include {
-            has_matches = true;
-            match_count += 1;
-            matching_line_indices.push(idx);
+/// Run the pattern over the whole buffer and select every line a match touches
+fn select_multiline(
+    config: &SearchConfig,
+    regex: &Regex,
+    buffer: &str,
+    line_count: usize,
+) -> Selection {
+    // Byte offset where each line starts, so match offsets can be mapped back to lines
+    let terminator = char::from(record_terminator(config));
+    let mut line_starts = vec![0];
+    line_starts.extend(buffer.match_indices(terminator).map(|(idx, _)| idx + 1));
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset) - 1;
+
+    let mut touched = Selection::new();
+    for mat in regex.find_iter(buffer) {
+        let first = line_of(mat.start());
+        let last = if mat.end() > mat.start() { line_of(mat.end() - 1) } else { first };
+        // A match at the very end of the input (or in empty input) is on no line
+        if first >= line_count {
+            continue;
+        }
 
-            // For files_with_matches/files_without_match modes, we just need to know if there are matches
-            if config.files_with_matches || config.files_without_match {
-                continue;
-            }
+        for idx in first..=last.min(line_count.saturating_sub(1)) {
+            let line_start = line_starts[idx];
+            let line_end = line_starts.get(idx + 1).map_or(buffer.len(), |&next| next - 1);
+            // Clip the match to this line, leaving out the line terminator
+            let line = &buffer[line_start..line_end];
+            let line_len = if config.null_data { line.len() } else { line.trim_end_matches('\r').len() };
+            let start = mat.start().max(line_start) - line_start;
+            let end = (mat.end().min(line_end) - line_start).min(line_len);
+            touched.entry(idx).or_default().push(start.min(end)..end);
         }
     }
 
+    if !config.invert_match {
+        return touched;
+    }
+    (0..line_count)
+        .filter(|idx| !touched.contains_key(idx))
+        .map(|idx| (idx, Vec::new()))
+        .collect()
+}
+
+/// Tag the match spans of a record with the pattern that produced each; matches of
+/// a single pattern are all tagged 0. Sorted by start, for highlighting.
+fn attribute_spans(
+    config: &SearchConfig,
+    matcher: &Matcher,
+    subject: &str,
+    spans: &[Range<usize>],
+) -> Vec<(Range<usize>, usize)> {
+    let Matcher::Expression(expression) = matcher else {
+        return spans.iter().map(|span| (span.clone(), 0)).collect();
+    };
+    if expression.term_count() == 1 || spans.is_empty() {
+        return spans.iter().map(|span| (span.clone(), 0)).collect();
+    }
+
+    let positive = expression.positive_terms();
+    let mut tagged: Vec<_> = term_spans(config, expression, subject)
+        .into_iter()
+        .enumerate()
+        .filter(|(term, _)| positive.contains(term))
+        .flat_map(|(term, spans)| spans.into_iter().map(move |span| (span, term)))
+        .collect();
+    tagged.sort_by_key(|(span, term)| (span.start, *term));
+    tagged
+}
+
+/// Comma-separated labels of the patterns behind some tagged spans, `-` for none
+fn pattern_labels(matcher: &Matcher, spans: &[(Range<usize>, usize)]) -> String {
+    let Matcher::Expression(expression) = matcher else {
+        return "-".to_string();
+    };
+    let terms: BTreeSet<usize> = spans.iter().map(|&(_, term)| term).collect();
+    if terms.is_empty() {
+        return "-".to_string();
+    }
+    let labels: Vec<&str> = terms.into_iter().map(|term| expression.labels()[term].as_str()).collect();
+    labels.join(",")
+}
+
+/// Report the selected records of one input; `file_path` is `None` for stdin.
+///
+/// Every record in `windows` is printed as well, as context unless it is selected.
+fn output_selection(
+    config: &SearchConfig,
+    matcher: &Matcher,
+    file_path: Option<&str>,
+    records: &[Record],
+    selection: &Selection,
+    windows: &[RangeInclusive<usize>],
+    writer: &mut impl Write,
+) -> Result<bool> {
+    let has_matches = !selection.is_empty();
+    let match_count = selection.len();
+
     // Handle count-only mode
     if config.count {
-        writeln!(writer, "{}:{}", file_path, match_count)?;
-        return Ok(match_count > 0);
+        let output = match file_path {
+            Some(path) => format!("{}{}{}", path, filename_separator(config, ':'), match_count),
+            None => match_count.to_string(),
+        };
+        write_record(config, writer, &output)?;
+        return Ok(has_matches);
     }
 
-    // Handle files_with_matches/files_without_match output
-    if config.files_with_matches && has_matches {
-        writeln!(writer, "{}", file_path)?;
-        return Ok(has_matches);
-    } else if config.files_without_match && !has_matches {
-        writeln!(writer, "{}", file_path)?;
+    // Handle files_with_matches/files_without_match output (they don't apply to stdin)
+    if config.files_with_matches || config.files_without_match {
+        if let Some(path) = file_path {
+            if has_matches == config.files_with_matches {
+                write_filename(config, writer, path)?;
+            }
+        }
         return Ok(has_matches);
     }
 
+    // Use context: if -C is specified, it overrides -A and -B
+    let before_context = if config.context > 0 { config.context } else { config.before_context };
+    let after_context = if config.context > 0 { config.context } else { config.after_context };
+
     // Output results with context
-    let mut output_lines = std::collections::BTreeSet::new(); // Use BTreeSet to keep lines in order
-    for &match_idx in &matching_line_indices {
-        let start_idx = if match_idx >= before_context { match_idx - before_context } else { 0 };
-        let end_idx = std::cmp::min(match_idx + after_context, lines.len() - 1);
+    let mut output_lines = BTreeSet::new(); // Use BTreeSet to keep lines in order
+    for &match_idx in selection.keys() {
+        let start_idx = match_idx.saturating_sub(before_context);
+        let end_idx = std::cmp::min(match_idx + after_context, records.len().saturating_sub(1));
 
         // Add this matching line and its context
         for idx in start_idx..=end_idx {
-            output_lines.insert((idx, match_idx == idx)); // (line_idx, is_match)
+            output_lines.insert((idx, selection.contains_key(&idx))); // (line_idx, is_match)
         }
     }
+    for window in windows {
+        for idx in window.clone() {
+            output_lines.insert((idx, selection.contains_key(&idx)));
+        }
+    }
+    if config.passthru {
+        output_lines.extend((0..records.len()).map(|idx| (idx, selection.contains_key(&idx))));
+    }
 
+    // A custom --format gets the whole line and each match separately, while the default
+    // layouts print the match or line with any labels in front as `{text}`
+    let (plain_template, column_template) = match &config.format {
+        Some(template) => (Cow::Borrowed(template), Cow::Borrowed(template)),
+        None => (Cow::Owned(default_template(config, false)), Cow::Owned(default_template(config, config.column))),
+    };
+
+    // Written just before the first line, so files without output get no heading
+    let mut heading = file_path.filter(|_| config.with_filename).zip(config.headings.as_ref());
+    // Context and --near windows come in groups of lines, separated where they aren't adjacent;
+    // JSON Lines output has no separator lines
+    let separator = config
+        .group_separator
+        .as_ref()
+        .filter(|_| !config.json && (before_context > 0 || after_context > 0 || !windows.is_empty()));
+    let mut previous_idx: Option<usize> = None;
     let mut output_count = 0;
     for (line_idx, is_match) in output_lines {
         // Check max count limit
@@ -198,35 +996,295 @@ pub fn search_file(
             }
         }
 
-        if is_match {
+        if let Some((path, headings)) = heading.take() {
+            headings.write(config, writer, path)?;
+        }
+        if let Some(separator) = separator.filter(|_| previous_idx.is_none_or(|previous| line_idx > previous + 1)) {
+            // Files under headings are set apart by blank lines instead
+            separator.write(config, writer, previous_idx.is_none() && config.headings.is_some())?;
+        }
+        previous_idx = Some(line_idx);
+
+        let record = &records[line_idx];
+        let line = &record.text;
+        // Byte offset in the input of a 1-based column of the record, or of the record itself
+        let offset_of = |column: Option<usize>| {
+            record.byte_offset.map(|offset| offset + column.map_or(0, |column| column - 1))
+        };
+        if config.json {
+            let spans = selection.get(&line_idx).map_or(&[][..], Vec::as_slice);
+            let output = format_json_line(config, matcher, file_path, record, is_match, spans);
+            write_record(config, writer, &output)?;
+        } else if is_match {
+            let spans = &selection[&line_idx];
+            let pattern_spans = attribute_spans(config, matcher, record.subject(), spans);
+            // Columns count from the start of the printed record where the subject appears in it
+            let column_of = |span: &Range<usize>| span.start + record.subject_offset().unwrap_or(0) + 1;
+            let captures_at = |span: &Range<usize>| {
+                let regex = matcher.regex().filter(|_| config.format.is_some())?;
+                regex
+                    .captures_at(record.subject(), span.start)
+                    .filter(|captures| captures.get(0).is_some_and(|mat| mat.start() == span.start))
+            };
+            // Highlight matches where they appear verbatim
+            let highlights: Vec<_> = match record.subject_offset() {
+                Some(offset) => pattern_spans
+                    .iter()
+                    .map(|(span, pattern)| (span.start + offset..span.end + offset, *pattern))
+                    .collect(),
+                None => Vec::new(),
+            };
+            let highlighted = display_line(config, line, &highlights, spans.len());
             // This is a matching line
             if config.only_matching {
                 // Extract only the matching parts
-                for mat in regex.find_iter(&lines[line_idx]) {
-                    writeln!(writer, "{}", mat.as_str())?;
+                for span in spans.iter().filter(|span| !span.is_empty()) {
+                    let matched = &record.subject()[span.clone()];
+                    let overlapping: Vec<_> = pattern_spans
+                        .iter()
+                        .filter(|(pattern_span, _)| pattern_span.start < span.end && span.start < pattern_span.end)
+                        .cloned()
+                        .collect();
+                    let labels = pattern_labels(matcher, &overlapping);
+                    let column = column_of(span);
+                    let url = hyperlink_url(config, file_path, Some(record.line_number), Some(column));
+                    let output = if config.format.is_some() {
+                        column_template.render(&Fields {
+                            path: file_path,
+                            line: record.line_number,
+                            column: Some(column),
+                            offset: offset_of(Some(column)),
+                            text: &highlighted,
+                            matched: Some(matched),
+                            id: Some(&labels),
+                            separator: &config.match_separator,
+                            captures: captures_at(span),
+                            colors: colors(config),
+                            link: url.as_deref(),
+                        })
+                    } else {
+                        let mut content = match match_distance(config, matcher, matched) {
+                            Some(distance) => Cow::Owned(format!("{}:{}", distance, matched)),
+                            None => Cow::Borrowed(matched),
+                        };
+                        if config.pattern_ids {
+                            content = Cow::Owned(format!("{}:{}", labels, content));
+                        }
+                        // Matches are printed bare, without the path and line prefixes
+                        content.into_owned()
+                    };
+                    write_record(config, writer, &output)?;
                 }
             } else {
-                // Output the full line with proper formatting
-                let original_line = &lines[line_idx];
-                let line_to_output = if use_colors(&config.color) && !config.only_matching {
-                    colorize_matches(original_line, &regex, &config.color)
+                let labels = pattern_labels(matcher, &pattern_spans);
+                let content = if config.pattern_ids && config.format.is_none() {
+                    Cow::Owned(format!("{}:{}", labels, highlighted))
                 } else {
-                    original_line.clone()
+                    Cow::Borrowed(highlighted.as_str())
                 };
-                
-                let output = format_match_with_content(config, file_path, line_idx + 1, &line_to_output);
-                writeln!(writer, "{}", output)?;
+                let first = spans.first();
+                let column = first.map(column_of);
+                // Inverted matches have no column
+                let template = if column.is_some() { &column_template } else { &plain_template };
+                let url = hyperlink_url(config, file_path, Some(record.line_number), column);
+                let output = template.render(&Fields {
+                    path: file_path,
+                    line: record.line_number,
+                    column,
+                    offset: offset_of(column),
+                    text: &content,
+                    matched: first.map(|span| &record.subject()[span.clone()]),
+                    id: Some(&labels),
+                    separator: &config.match_separator,
+                    captures: first.and_then(captures_at),
+                    colors: colors(config),
+                    link: url.as_deref(),
+                });
+                write_record(config, writer, &output)?;
             }
         } else {
             // This is just context, output with dashes to separate
-            let output = format_context_line(config, file_path, line_idx + 1, &lines[line_idx]);
-            writeln!(writer, "{}", output)?;
+            let text = display_context_line(config, line);
+            let url = hyperlink_url(config, file_path, Some(record.line_number), None);
+            let output = plain_template.render(&Fields {
+                path: file_path,
+                line: record.line_number,
+                offset: offset_of(None),
+                text: &text,
+                separator: &config.context_separator,
+                colors: colors(config),
+                link: url.as_deref(),
+                ..Fields::default()
+            });
+            write_record(config, writer, &output)?;
         }
     }
 
     Ok(has_matches)
 }
 
+/// Search one input stream; `file_path` is `None` for stdin.
+///
+/// `skipped_lines` and `skipped_bytes` are the lines and bytes already skipped before the
+/// reader's position.
+fn search_reader(
+    config: &SearchConfig,
+    reader: impl BufRead,
+    file_path: Option<&str>,
+    skipped_lines: usize,
+    skipped_bytes: usize,
+    writer: &mut impl Write,
+) -> Result<bool> {
+    let matcher = build_matcher(config)?;
+    let (mut records, buffer) = read_input(config, reader, file_path.unwrap_or("(standard input)"))?;
+
+    for record in &mut records {
+        record.line_number += skipped_lines;
+        if let Some(offset) = &mut record.byte_offset {
+            *offset += skipped_bytes;
+        }
+    }
+    if let Some(filter) = &config.time_filter {
+        records = filter_time_range(records, filter);
+    }
+
+    let mut windows = Vec::new();
+    let mut selection = match (&buffer, &matcher, config.proximity) {
+        (Some(buffer), _, _) => {
+            let regex = matcher.regex().ok_or_else(|| {
+                PergError::InvalidPattern("multiline mode needs a regular expression".to_string())
+            })?;
+            select_multiline(config, regex, buffer, records.len())
+        }
+        (None, Matcher::Expression(expression), Some(proximity)) => {
+            let (selection, near_windows) = select_near(config, expression, &records, proximity);
+            windows = near_windows;
+            selection
+        }
+        (None, _, _) => select_records(config, &matcher, &records),
+    };
+    if let Matcher::Expression(expression) = &matcher {
+        if config.all_match && !matches_all_patterns(config, expression, &records) {
+            selection.clear();
+        }
+    }
+
+    if let (Some(stats), Matcher::Expression(expression)) = (&config.pattern_stats, &matcher) {
+        for &idx in selection.keys() {
+            let spans = term_spans(config, expression, records[idx].subject());
+            for (term, _) in spans.iter().enumerate().filter(|(_, spans)| !spans.is_empty()) {
+                stats.record(term);
+            }
+        }
+    }
+
+    if let Some(frequencies) = &config.frequencies {
+        let values = selected_values(config, &matcher, &records, &selection)?;
+        frequencies.add(file_path.unwrap_or("(standard input)"), values);
+        return Ok(!selection.is_empty());
+    }
+    if let Some(extractor) = &config.extract {
+        let regex = matcher.regex().ok_or_else(|| {
+            PergError::InvalidPattern("--extract needs a regular expression".to_string())
+        })?;
+        let path = file_path.filter(|_| config.with_filename);
+        for &idx in selection.keys() {
+            let record = &records[idx];
+            let line_number = Some(record.line_number).filter(|_| config.line_number);
+            for row in extractor.rows(regex, path, line_number, record.subject()) {
+                writeln!(writer, "{}", row)?;
+            }
+        }
+        return Ok(!selection.is_empty());
+    }
+
+    output_selection(config, &matcher, file_path, &records, &selection, &windows, writer)
+}
+
+/// Look up a capture group by number or name
+pub(crate) fn capture_group<'h>(captures: &Captures<'h>, group: &str) -> Option<Match<'h>> {
+    match group.parse::<usize>() {
+        Ok(number) => captures.get(number),
+        Err(_) => captures.name(group),
+    }
+}
+
+/// Texts of the selected matches: the capture group when one is chosen, otherwise each match,
+/// or the whole record when it was selected without a match (`-v`)
+fn selected_values<'a>(
+    config: &SearchConfig,
+    matcher: &Matcher,
+    records: &'a [Record],
+    selection: &Selection,
+) -> Result<Vec<&'a str>> {
+    let mut values = Vec::new();
+    for (&idx, spans) in selection {
+        let subject = records[idx].subject();
+        match &config.capture_group {
+            Some(group) => {
+                let regex = matcher.regex().ok_or_else(|| {
+                    PergError::InvalidPattern("capture groups need a regular expression".to_string())
+                })?;
+                values.extend(regex.captures_iter(subject).filter_map(|caps| capture_group(&caps, group)).map(|m| m.as_str()));
+            }
+            None if spans.iter().any(|span| !span.is_empty()) => {
+                values.extend(spans.iter().filter(|span| !span.is_empty()).map(|span| &subject[span.clone()]));
+            }
+            None => values.push(subject),
+        }
+    }
+    Ok(values)
+}
+
+/// Search for pattern in a single file
+pub fn search_file(
+    config: &SearchConfig,
+    file_path: &str,
+    writer: &mut impl Write,
+) -> Result<bool> {
+    let path = Path::new(file_path);
+
+    // Handle directory case for files_with_matches/files_without_match
+    if path.is_dir() && (config.files_with_matches || config.files_without_match) {
+        // For directories in these modes, we consider them as having no matches
+        // since directories themselves don't contain searchable text
+        if config.files_without_match {
+            write_filename(config, writer, file_path)?;
+        }
+        return Ok(false);
+    }
+
+    let mut file = File::open(path).map_err(|_| PergError::FileNotFound(file_path.to_string()))?;
+    let buffered = config.multiline
+        || config.record_separator.is_some()
+        || config.json_path.is_some()
+        || config.key_path.is_some();
+    if buffered && file.metadata()?.len() > MAX_BUFFERED_BYTES {
+        return Err(PergError::InputTooLarge(file_path.to_string()));
+    }
+
+    // Skip straight to the start of the time window in line-oriented regular files
+    let mut skipped_lines = 0;
+    let mut skipped_bytes = 0;
+    if let Some(filter) = &config.time_filter {
+        if !buffered && !config.null_data && file.metadata()?.is_file() {
+            skipped_lines = filter.seek_to_window(&mut file, prints_line_numbers(config))?;
+            skipped_bytes = file.stream_position()? as usize;
+        }
+    }
+
+    search_reader(config, BufReader::new(file), Some(file_path), skipped_lines, skipped_bytes, writer)
+}
+
+/// Whether any output can show a line number, so lines skipped by a seek must be counted
+fn prints_line_numbers(config: &SearchConfig) -> bool {
+    config.line_number
+        || config.json
+        || config.headings.is_some()
+        || config.format.as_ref().is_some_and(|template| template.uses(&Placeholder::Line))
+        || (config.hyperlinks.is_some() && colors(config).is_some())
+}
+
 /// Search for pattern in multiple files/directories
 pub fn search_paths(
     config: &SearchConfig,
@@ -277,22 +1335,23 @@ pub fn search_paths(
     let mut effective_config = config.clone();
     effective_config.with_filename = should_show_filename;
 
-    for (i, file_path) in all_files.iter().enumerate() {
-        if let Err(err) = search_file(&effective_config, file_path, writer) {
+    for file_path in &all_files {
+        let result = search_file(&effective_config, file_path, writer);
+        for warning in effective_config.take_warnings() {
+            if !no_messages {
+                eprintln!("perg: {}: {}", file_path, warning);
+            }
+        }
+        if let Err(err) = result {
             if !no_messages {
                 eprintln!("perg: {}: {}", file_path, err);
             }
             // Return error for critical failures like file not found or regex errors
             match err {
-                PergError::FileNotFound(_) | PergError::Regex(_) => return Err(err),
+                PergError::FileNotFound(_) | PergError::Regex(_) | PergError::InvalidPattern(_) => return Err(err),
                 _ => {} // Continue for other errors like I/O errors
             }
         }
-        
-        // Add separator between files if context is enabled and there are multiple files
-        if i < all_files.len() - 1 && (config.before_context > 0 || config.after_context > 0 || config.context > 0) {
-            writeln!(writer, "--")?;
-        }
     }
 
     Ok(())
@@ -301,206 +1360,64 @@ pub fn search_paths(
 /// Search stdin for the pattern
 pub fn search_stdin(config: &SearchConfig, writer: &mut impl Write) -> Result<()> {
     let stdin = stdin();
-    let reader = stdin.lock();
-
-    let pattern = if config.ignore_case {
-        format!("(?i){}", config.pattern)
-    } else {
-        config.pattern.clone()
-    };
-
-    let regex = Regex::new(&pattern)?;
-
-    let mut match_count = 0;
-    let mut _line_number = 0;
-    let mut lines: Vec<String> = Vec::new();
-    let mut matching_line_indices = Vec::new();
-    
-    // Read all lines to enable context functionality
-    for line_result in reader.lines() {
-        lines.push(line_result?);
-        _line_number += 1;
-    }
-
-    // Use context: if -C is specified, it overrides -A and -B
-    let before_context = if config.context > 0 { config.context } else { config.before_context };
-    let after_context = if config.context > 0 { config.context } else { config.after_context };
-
-    // First pass: find all matching lines
-    for (idx, line) in lines.iter().enumerate() {
-        let matches = regex.is_match(line);
-
-        // Apply invert match logic
-        let should_include = if config.invert_match { !matches } else { matches };
-
-        if should_include {
-            match_count += 1;
-            matching_line_indices.push(idx);
-
-            // For files_with_matches/files_without_match modes, we can't use stdin
-            if config.files_with_matches || config.files_without_match {
-                // For stdin, these modes don't make sense, so we just continue
-                continue;
-            }
-        }
-    }
-
-    // Handle count-only mode
-    if config.count {
-        writeln!(writer, "{}", match_count)?;
-        return Ok(());
-    }
-
-    // Handle files_with_matches/files_without_match modes (they don't make sense with stdin)
-    if config.files_with_matches || config.files_without_match {
-        // These modes don't apply to stdin
-        return Ok(());
-    }
-
-    // Output results with context
-    let mut output_lines = std::collections::BTreeSet::new(); // Use BTreeSet to keep lines in order
-    for &match_idx in &matching_line_indices {
-        let start_idx = if match_idx >= before_context { match_idx - before_context } else { 0 };
-        let end_idx = std::cmp::min(match_idx + after_context, lines.len() - 1);
-
-        // Add this matching line and its context
-        for idx in start_idx..=end_idx {
-            output_lines.insert((idx, match_idx == idx)); // (line_idx, is_match)
-        }
-    }
-
-    let mut output_count = 0;
-    for (line_idx, is_match) in output_lines {
-        // Check max count limit
-        if is_match {
-            if let Some(max) = config.max_count {
-                if output_count >= max {
-                    break;
-                }
-                output_count += 1;
-            }
-        }
-
-        if is_match {
-            // This is a matching line
-            if config.only_matching {
-                // Extract only the matching parts
-                for mat in regex.find_iter(&lines[line_idx]) {
-                    writeln!(writer, "{}", mat.as_str())?;
-                }
-            } else {
-                // Output the full line with proper formatting
-                let original_line = &lines[line_idx];
-                let line_to_output = if use_colors(&config.color) && !config.only_matching {
-                    colorize_matches(original_line, &regex, &config.color)
-                } else {
-                    original_line.clone()
-                };
-                
-                let output = format_line_with_content(config, line_idx + 1, &line_to_output);
-                writeln!(writer, "{}", output)?;
-            }
-        } else {
-            // This is just context, output with dashes to separate
-            let output = format_context_line_stdin(config, line_idx + 1, &lines[line_idx]);
-            writeln!(writer, "{}", output)?;
-        }
-    }
-
+    search_reader(config, stdin.lock(), None, 0, 0, writer)?;
     Ok(())
 }
 
-/// Format a single match result for output
-fn format_match(config: &SearchConfig, file_path: &str, line_number: usize, line: &str) -> String {
-    let mut output = String::new();
-
-    if config.with_filename {
-        output.push_str(file_path);
-        output.push_str(":");
-    }
-
-    if config.line_number {
-        output.push_str(&line_number.to_string());
-        output.push_str(":");
-    }
-
-    output.push_str(line);
-    output
-}
-
-/// Format a single match result for output with custom content
-fn format_match_with_content(config: &SearchConfig, file_path: &str, line_number: usize, line: &str) -> String {
-    let mut output = String::new();
-
-    if config.with_filename {
-        output.push_str(file_path);
-        output.push_str(":");
+/// Template for the default `path:line:column:content` layout, where `{text}` is the content
+/// and `{sep}` is `:` after matching lines and `-` after context lines
+fn default_template(config: &SearchConfig, column: bool) -> Template {
+    let mut spec = String::new();
+    let heading = config.with_filename && config.headings.is_some();
+    if config.with_filename && !heading {
+        spec.push_str(if config.null { "{path}\\0" } else { "{path}{sep}" });
     }
-
-    if config.line_number {
-        output.push_str(&line_number.to_string());
-        output.push_str(":");
+    if config.line_number || heading {
+        spec.push_str("{line}{sep}");
     }
-
-    output.push_str(line);
-    output
-}
-
-/// Format a line from stdin (without filename prefix)
-fn format_line(config: &SearchConfig, line_number: usize, line: &str) -> String {
-    let mut output = String::new();
-
-    if config.line_number {
-        output.push_str(&line_number.to_string());
-        output.push_str(":");
+    if column {
+        spec.push_str("{col}{sep}");
     }
-
-    output.push_str(line);
-    output
+    spec.push_str("{text}");
+    Template::parse(&spec).expect("valid default template")
 }
 
-/// Format a line from stdin with custom content
-fn format_line_with_content(config: &SearchConfig, line_number: usize, line: &str) -> String {
-    let mut output = String::new();
-
-    if config.line_number {
-        output.push_str(&line_number.to_string());
-        output.push_str(":");
-    }
-
-    output.push_str(line);
-    output
-}
-
-/// Format a context line for file output
-fn format_context_line(config: &SearchConfig, file_path: &str, line_number: usize, line: &str) -> String {
-    let mut output = String::new();
-
-    if config.with_filename {
-        output.push_str(file_path);
-        output.push_str("-");
-    }
-
-    if config.line_number {
-        output.push_str(&line_number.to_string());
-        output.push_str("-");
-    }
-
-    output.push_str(line);
-    output
-}
-
-/// Format a context line from stdin (without filename prefix)
-fn format_context_line_stdin(config: &SearchConfig, line_number: usize, line: &str) -> String {
-    let mut output = String::new();
-
-    if config.line_number {
-        output.push_str(&line_number.to_string());
-        output.push_str("-");
+/// Format an output line as a JSON object; match offsets are bytes into `text`,
+/// and are left out when the matched value doesn't appear verbatim in it
+fn format_json_line(
+    config: &SearchConfig,
+    matcher: &Matcher,
+    file_path: Option<&str>,
+    record: &Record,
+    is_match: bool,
+    spans: &[Range<usize>],
+) -> String {
+    let matches: Vec<_> = spans
+        .iter()
+        .map(|span| {
+            let matched = &record.subject()[span.clone()];
+            let mut object = serde_json::json!({ "text": matched });
+            if let Some(offset) = record.subject_offset() {
+                object["start"] = (span.start + offset).into();
+                object["end"] = (span.end + offset).into();
+            }
+            if let Some(distance) = match_distance(config, matcher, matched) {
+                object["distance"] = distance.into();
+            }
+            object
+        })
+        .collect();
+
+    let mut object = serde_json::json!({
+        "type": if is_match { "match" } else { "context" },
+        "path": file_path,
+        "line_number": record.line_number,
+        "text": record.text,
+    });
+    if is_match {
+        object["matches"] = matches.into();
     }
-
-    output.push_str(line);
-    output
+    object.to_string()
 }
 
 #[cfg(test)]
@@ -607,4 +1524,25 @@ mod tests {
         assert!(output_str.contains("line 3"));
         assert!(!output_str.contains("test line 2"));
     }
+
+    #[test]
+    fn test_split_records_line_numbers() {
+        let separator = Regex::new(PARAGRAPH_SEPARATOR).unwrap();
+        let records = split_records("\nfirst\nstill first\n\n\nsecond\n", &separator);
+
+        let starts: Vec<_> = records.iter().map(|r| (r.line_number, r.text.as_str())).collect();
+        assert_eq!(starts, vec![(2, "first\nstill first"), (6, "second")]);
+    }
+
+    #[test]
+    fn test_preview_window() {
+        let line = "aaaaaaaaaa match bbbbbbbbbb";
+        assert_eq!(&line[preview_window(line, 9, 11..16)], "a match b");
+        assert_eq!(&line[preview_window(line, 8, 0..1)], "aaaaaaaa");
+        assert_eq!(&line[preview_window(line, 8, 26..27)], "bbbbbbbb");
+
+        // Windows never split a character
+        let line = "ééééé";
+        assert_eq!(&line[preview_window(line, 5, 4..6)], "éé");
+    }
 }
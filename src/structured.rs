@@ -0,0 +1,488 @@
+//! Structural search in JSON, YAML and TOML documents by key path.
+//!
+//! Key paths look like `.request.headers.user_agent`, `.items[*].name`,
+//! `.items[0]` or `.["key.with.dots"]`. The pattern is tested only against the
+//! values the path selects. JSON documents are printed whole, while YAML and
+//! TOML matches are reported at the source line of the matched value.
+
+use crate::error::{PergError, Result};
+use crate::search::{Field, Record};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::Marker;
+
+/// Configuration file formats understood by `--key-path`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StructuredFormat {
+    Yaml,
+    Toml,
+}
+
+impl StructuredFormat {
+    /// Look up a format by name, as given to `--input-format`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "yaml" | "yml" => Some(StructuredFormat::Yaml),
+            "toml" => Some(StructuredFormat::Toml),
+            _ => None,
+        }
+    }
+
+    /// Guess the format from a file extension, defaulting to YAML (which also covers JSON)
+    pub fn detect(file_path: &str) -> Self {
+        Path::new(file_path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Self::from_name)
+            .unwrap_or(StructuredFormat::Yaml)
+    }
+}
+
+/// One step of a key path
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    /// Object member by name
+    Key(String),
+    /// Array element by position
+    Index(usize),
+    /// Every member of an object or element of an array
+    Wildcard,
+}
+
+/// Path to the values a structured search tests, e.g. `.items[*].name`
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyPath {
+    pub segments: Vec<Segment>,
+}
+
+impl KeyPath {
+    /// Parse a key path such as `.request.headers["user-agent"]` or `.items[*].name`
+    pub fn parse(path: &str) -> Result<Self> {
+        let invalid = || PergError::InvalidPattern(format!("invalid key path: {}", path));
+        let mut segments = Vec::new();
+        let path = path.trim();
+        // The leading `.` is optional: `items[*].name` is the same as `.items[*].name`
+        let normalized = if path.starts_with(['.', '[']) { path.to_string() } else { format!(".{}", path) };
+        let mut rest = normalized.as_str();
+
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('[') {
+                let end = if let Some(quoted) = after.strip_prefix('"') {
+                    quoted.find('"').map(|idx| idx + 2).ok_or_else(invalid)?
+                } else {
+                    after.find(']').ok_or_else(invalid)?
+                };
+                let inner = &after[..end];
+                segments.push(match inner {
+                    "*" => Segment::Wildcard,
+                    quoted if quoted.len() >= 2 && quoted.starts_with('"') => {
+                        Segment::Key(quoted[1..quoted.len() - 1].to_string())
+                    }
+                    index => Segment::Index(index.parse().map_err(|_| invalid())?),
+                });
+                rest = after[end..].strip_prefix(']').ok_or_else(invalid)?;
+            } else if let Some(after) = rest.strip_prefix('.') {
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                match &after[..end] {
+                    // A lone `.` selects the root, and `.[` introduces a bracketed segment
+                    "" if end == after.len() || after[end..].starts_with('[') => {}
+                    "" => return Err(invalid()),
+                    "*" => segments.push(Segment::Wildcard),
+                    key => segments.push(Segment::Key(key.to_string())),
+                }
+                rest = &after[end..];
+            } else {
+                return Err(invalid());
+            }
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// Values in a JSON document selected by this path
+    pub fn select_json<'a>(&self, root: &'a Value) -> Vec<&'a Value> {
+        let mut current = vec![root];
+        for segment in &self.segments {
+            current = current
+                .into_iter()
+                .flat_map(|value| -> Vec<&'a Value> {
+                    match (segment, value) {
+                        (Segment::Key(key), Value::Object(map)) => map.get(key).into_iter().collect(),
+                        (Segment::Index(idx), Value::Array(items)) => items.get(*idx).into_iter().collect(),
+                        (Segment::Wildcard, Value::Object(map)) => map.values().collect(),
+                        (Segment::Wildcard, Value::Array(items)) => items.iter().collect(),
+                        _ => Vec::new(),
+                    }
+                })
+                .collect();
+        }
+        current
+    }
+}
+
+impl KeyPath {
+    /// Nodes in a YAML or TOML document selected by this path
+    fn select_nodes<'a>(&self, root: &'a Node) -> Vec<&'a Node> {
+        let mut current = vec![root];
+        for segment in &self.segments {
+            current = current
+                .into_iter()
+                .flat_map(|node| -> Vec<&'a Node> {
+                    match (segment, &node.kind) {
+                        (Segment::Key(key), NodeKind::Map(entries)) => entries
+                            .iter()
+                            .filter(|(name, _)| name == key)
+                            .map(|(_, value)| value)
+                            .collect(),
+                        (Segment::Index(idx), NodeKind::List(items)) => items.get(*idx).into_iter().collect(),
+                        (Segment::Wildcard, NodeKind::Map(entries)) => entries.iter().map(|(_, value)| value).collect(),
+                        (Segment::Wildcard, NodeKind::List(items)) => items.iter().collect(),
+                        _ => Vec::new(),
+                    }
+                })
+                .collect();
+        }
+        current
+    }
+}
+
+/// A YAML or TOML value with the source line it starts on
+#[derive(Debug, Clone)]
+struct Node {
+    line: usize,
+    kind: NodeKind,
+}
+
+#[derive(Debug, Clone)]
+enum NodeKind {
+    Scalar(String),
+    List(Vec<Node>),
+    Map(Vec<(String, Node)>),
+}
+
+impl Node {
+    /// Text the pattern is tested against: scalars as written, containers in flow style
+    fn text(&self) -> String {
+        match &self.kind {
+            NodeKind::Scalar(text) => text.clone(),
+            NodeKind::List(items) => {
+                let items: Vec<_> = items.iter().map(Node::text).collect();
+                format!("[{}]", items.join(", "))
+            }
+            NodeKind::Map(entries) => {
+                let entries: Vec<_> = entries.iter().map(|(key, value)| format!("{}: {}", key, value.text())).collect();
+                format!("{{{}}}", entries.join(", "))
+            }
+        }
+    }
+}
+
+/// Container being filled while replaying YAML parser events
+enum Frame {
+    List { line: usize, anchor: usize, items: Vec<Node> },
+    Map { line: usize, anchor: usize, entries: Vec<(String, Node)>, key: Option<String> },
+}
+
+/// Builds line-annotated YAML documents from parser events
+#[derive(Default)]
+struct YamlBuilder {
+    documents: Vec<Node>,
+    stack: Vec<Frame>,
+    anchors: HashMap<usize, Node>,
+}
+
+impl YamlBuilder {
+    fn insert(&mut self, node: Node, anchor: usize) {
+        if anchor > 0 {
+            self.anchors.insert(anchor, node.clone());
+        }
+        match self.stack.last_mut() {
+            None => self.documents.push(node),
+            Some(Frame::List { items, .. }) => items.push(node),
+            Some(Frame::Map { entries, key, .. }) => match key.take() {
+                Some(key) => entries.push((key, node)),
+                None => *key = Some(node.text()),
+            },
+        }
+    }
+}
+
+impl MarkedEventReceiver for YamlBuilder {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        let line = mark.line();
+        match event {
+            Event::Scalar(text, _, anchor, _) => self.insert(Node { line, kind: NodeKind::Scalar(text) }, anchor),
+            Event::Alias(anchor) => {
+                let node = self.anchors.get(&anchor).cloned().unwrap_or(Node {
+                    line,
+                    kind: NodeKind::Scalar(String::new()),
+                });
+                self.insert(node, 0);
+            }
+            Event::SequenceStart(anchor, _) => self.stack.push(Frame::List { line, anchor, items: Vec::new() }),
+            Event::MappingStart(anchor, _) => self.stack.push(Frame::Map {
+                line,
+                anchor,
+                entries: Vec::new(),
+                key: None,
+            }),
+            Event::SequenceEnd | Event::MappingEnd => {
+                let (node, anchor) = match self.stack.pop() {
+                    Some(Frame::List { line, anchor, items }) => (Node { line, kind: NodeKind::List(items) }, anchor),
+                    Some(Frame::Map { line, anchor, entries, .. }) => (Node { line, kind: NodeKind::Map(entries) }, anchor),
+                    None => return,
+                };
+                self.insert(node, anchor);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Parse every document in a YAML stream
+fn parse_yaml(buffer: &str) -> Result<Vec<Node>> {
+    let mut builder = YamlBuilder::default();
+    Parser::new_from_str(buffer)
+        .load(&mut builder, true)
+        .map_err(|err| PergError::Parse(format!("invalid YAML: {}", err)))?;
+    Ok(builder.documents)
+}
+
+/// Parse a TOML document, recording the line each value starts on
+fn parse_toml(buffer: &str) -> Result<Node> {
+    let converter = TomlConverter { source: buffer };
+    let document = toml_edit::Document::parse(buffer).map_err(|err| {
+        let line = converter.line(err.span()).unwrap_or(1);
+        PergError::Parse(format!("invalid TOML at line {}: {}", line, err.message()))
+    })?;
+    Ok(converter.table(document.as_table()))
+}
+
+/// Converts parsed TOML into line-annotated nodes using the spans kept by the parser
+struct TomlConverter<'a> {
+    source: &'a str,
+}
+
+impl TomlConverter<'_> {
+    fn line(&self, span: Option<Range<usize>>) -> Option<usize> {
+        span.map(|span| self.source[..span.start].matches('\n').count() + 1)
+    }
+
+    fn value(&self, value: &toml_edit::Value) -> Node {
+        let kind = match value {
+            toml_edit::Value::String(text) => NodeKind::Scalar(text.value().clone()),
+            toml_edit::Value::Integer(number) => NodeKind::Scalar(number.value().to_string()),
+            toml_edit::Value::Float(number) => NodeKind::Scalar(number.value().to_string()),
+            toml_edit::Value::Boolean(flag) => NodeKind::Scalar(flag.value().to_string()),
+            toml_edit::Value::Datetime(datetime) => NodeKind::Scalar(datetime.value().to_string()),
+            toml_edit::Value::Array(items) => NodeKind::List(items.iter().map(|item| self.value(item)).collect()),
+            toml_edit::Value::InlineTable(table) => NodeKind::Map(
+                table
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), self.value(value)))
+                    .collect(),
+            ),
+        };
+        Node { line: self.line(value.span()).unwrap_or(1), kind }
+    }
+
+    fn table(&self, table: &toml_edit::Table) -> Node {
+        let entries: Vec<(String, Node)> = table
+            .iter()
+            .filter_map(|(key, item)| self.item(item).map(|node| (key.to_string(), node)))
+            .collect();
+        // Implicit tables have no span of their own, so use their first entry's line
+        let line = self
+            .line(table.span())
+            .or_else(|| entries.first().map(|(_, node)| node.line))
+            .unwrap_or(1);
+        Node { line, kind: NodeKind::Map(entries) }
+    }
+
+    fn item(&self, item: &toml_edit::Item) -> Option<Node> {
+        match item {
+            toml_edit::Item::None => None,
+            toml_edit::Item::Value(value) => Some(self.value(value)),
+            toml_edit::Item::Table(table) => Some(self.table(table)),
+            toml_edit::Item::ArrayOfTables(tables) => {
+                let items: Vec<Node> = tables.iter().map(|table| self.table(table)).collect();
+                let line = items.first().map_or(1, |node| node.line);
+                Some(Node { line, kind: NodeKind::List(items) })
+            }
+        }
+    }
+}
+
+/// Parse a YAML or TOML document and turn each value at `path` into a record
+/// holding the value's source line.
+pub(crate) fn read_document_records(buffer: &str, path: &KeyPath, format: StructuredFormat) -> Result<Vec<Record>> {
+    let documents = match format {
+        StructuredFormat::Yaml => parse_yaml(buffer)?,
+        StructuredFormat::Toml => vec![parse_toml(buffer)?],
+    };
+    let lines: Vec<&str> = buffer.lines().collect();
+
+    let mut records = Vec::new();
+    for document in &documents {
+        for node in path.select_nodes(document) {
+            let text = lines.get(node.line.saturating_sub(1)).copied().unwrap_or_default().to_string();
+            let value = node.text();
+            let offset = text.find(value.as_str()).filter(|_| !value.is_empty());
+            records.push(Record {
+                line_number: node.line,
+                text,
+                field: Some(Field { value, offset }),
+                byte_offset: None,
+            });
+        }
+    }
+    records.sort_by_key(|record| record.line_number);
+    Ok(records)
+}
+
+/// Text the pattern is tested against for a selected JSON value
+fn json_value_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Error for a malformed JSON document starting on `line`; serde's own position is left
+/// out, being relative to where parsing last restarted
+fn invalid_json(err: &serde_json::Error, line: usize) -> PergError {
+    let message = err.to_string();
+    let position = format!(" at line {} column {}", err.line(), err.column());
+    let message = message.strip_suffix(&position).unwrap_or(&message);
+    PergError::Parse(format!("line {}: invalid JSON: {}", line, message))
+}
+
+/// Split JSON Lines or concatenated JSON documents into records, keyed by the values at `path`.
+///
+/// Selected values are joined with newlines into the record's field, so a record
+/// matches when any of its values does. A malformed document that fails on the line
+/// it starts on, or any malformed line of input that looks like JSON Lines, is skipped
+/// and returned as a warning with its line number; other malformed input is an error.
+pub(crate) fn read_json_records(buffer: &str, path: &KeyPath, pretty: bool) -> Result<(Vec<Record>, Vec<PergError>)> {
+    let mut records = Vec::new();
+    let mut warnings = Vec::new();
+    let mut line_number = 1;
+    let mut last = 0;
+    // JSON Lines until a document spans several lines
+    let mut json_lines = buffer
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .is_some_and(|line| serde_json::from_str::<Value>(line).is_ok());
+
+    // Parsing restarts after each skipped line
+    'lines: loop {
+        let mut stream = serde_json::Deserializer::from_str(&buffer[last..]).into_iter::<Value>();
+        let base = last;
+        let base_line = line_number;
+        while let Some(document) = stream.next() {
+            let rest = &buffer[last..];
+            let skipped = rest.len() - rest.trim_start().len();
+            let start = last + skipped;
+            let start_line = line_number + rest[..skipped].matches('\n').count();
+
+            let document = match document {
+                Ok(document) => document,
+                Err(err) => {
+                    let error_line = base_line + err.line().saturating_sub(1);
+                    if !json_lines && error_line != start_line {
+                        return Err(invalid_json(&err, error_line));
+                    }
+                    warnings.push(invalid_json(&err, start_line));
+                    last = buffer[start..].find('\n').map_or(buffer.len(), |idx| start + idx + 1);
+                    line_number = start_line + 1;
+                    continue 'lines;
+                }
+            };
+            let end = base + stream.byte_offset();
+            let text = &buffer[start..end];
+            json_lines &= !text.contains('\n');
+            line_number += buffer[last..end].matches('\n').count();
+            last = end;
+
+            let value = path
+                .select_json(&document)
+                .into_iter()
+                .map(json_value_text)
+                .collect::<Vec<_>>()
+                .join("\n");
+            let text = if pretty {
+                serde_json::to_string_pretty(&document).map_err(|err| PergError::Parse(err.to_string()))?
+            } else {
+                text.to_string()
+            };
+
+            records.push(Record {
+                line_number: start_line,
+                text,
+                field: Some(Field { value, offset: None }),
+                byte_offset: None,
+            });
+        }
+        return Ok((records, warnings));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key_path() {
+        let path = KeyPath::parse(r#".items[*].name["x.y"][2]"#).unwrap();
+        assert_eq!(
+            path.segments,
+            vec![
+                Segment::Key("items".to_string()),
+                Segment::Wildcard,
+                Segment::Key("name".to_string()),
+                Segment::Key("x.y".to_string()),
+                Segment::Index(2),
+            ]
+        );
+        assert_eq!(KeyPath::parse(".").unwrap().segments, Vec::new());
+        assert_eq!(KeyPath::parse("items").unwrap().segments, vec![Segment::Key("items".to_string())]);
+        assert!(KeyPath::parse(".items..name").is_err());
+        assert!(KeyPath::parse(".items[one]").is_err());
+    }
+
+    #[test]
+    fn test_yaml_values_report_source_lines() {
+        let yaml = "kind: Pod\nspec:\n  containers:\n    - name: web\n      image: nginx:latest\n    - name: db\n      image: postgres:16\n---\nspec:\n  containers: []\n";
+        let path = KeyPath::parse(".spec.containers[*].image").unwrap();
+
+        let records = read_document_records(yaml, &path, StructuredFormat::Yaml).unwrap();
+        let found: Vec<_> = records.iter().map(|r| (r.line_number, r.text.as_str())).collect();
+        assert_eq!(found, vec![(5, "      image: nginx:latest"), (7, "      image: postgres:16")]);
+    }
+
+    #[test]
+    fn test_toml_values_and_parse_errors() {
+        let toml = "[package]\nname = \"perg\"\n\n[[bin]]\nname = \"perg\"\npath = \"src/main.rs\"\n";
+        let path = KeyPath::parse("bin[*].path").unwrap();
+
+        let records = read_document_records(toml, &path, StructuredFormat::Toml).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].line_number, 6);
+        assert_eq!(records[0].field.as_ref().unwrap().value, "src/main.rs");
+
+        let err = read_document_records("a = \n", &path, StructuredFormat::Toml).unwrap_err();
+        assert!(matches!(err, PergError::Parse(message) if message.contains("line 1")));
+    }
+
+    #[test]
+    fn test_select_json_values() {
+        let document: Value = serde_json::from_str(r#"{"items": [{"name": "a"}, {"name": 2}, {}]}"#).unwrap();
+        let path = KeyPath::parse(".items[*].name").unwrap();
+
+        let values: Vec<_> = path.select_json(&document).into_iter().map(json_value_text).collect();
+        assert_eq!(values, vec!["a", "2"]);
+    }
+}
@@ -0,0 +1,123 @@
+//! Field-aware search in CSV and TSV files.
+//!
+//! Each CSV record (which may span several lines when quoted fields contain
+//! newlines) becomes one search record, and the pattern is tested against a
+//! single column instead of the whole line.
+
+use crate::error::{PergError, Result};
+use crate::search::{Field, Record};
+use std::io::Read;
+
+/// How CSV input is split into records and which column is searched
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    /// Column to test, as a header name or a 1-based column number
+    pub column: String,
+    /// Field delimiter, `,` for CSV and a tab for TSV
+    pub delimiter: u8,
+    /// Whether the first record is a header row naming the columns
+    pub has_header: bool,
+    /// Columns to print instead of the full record
+    pub output_columns: Vec<String>,
+}
+
+impl CsvOptions {
+    pub fn new(column: String, delimiter: u8) -> Self {
+        Self {
+            column,
+            delimiter,
+            has_header: true,
+            output_columns: Vec::new(),
+        }
+    }
+}
+
+/// Read CSV records, pairing each with the value of the searched column
+pub(crate) fn read_records(reader: impl Read, options: &CsvOptions) -> Result<Vec<Record>> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(reader);
+    let mut rows = csv_reader.records();
+
+    let header = if options.has_header {
+        match rows.next() {
+            Some(row) => Some(row.map_err(parse_error)?),
+            None => return Ok(Vec::new()),
+        }
+    } else {
+        None
+    };
+
+    let column = resolve_column(&options.column, header.as_ref())?;
+    let output_columns = options
+        .output_columns
+        .iter()
+        .map(|name| resolve_column(name, header.as_ref()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut records = Vec::new();
+    for row in rows {
+        let row = row.map_err(parse_error)?;
+        let line_number = row.position().map_or(0, |position| position.line() as usize);
+        let value = row.get(column).unwrap_or_default().to_string();
+
+        let columns: Vec<usize> = if output_columns.is_empty() {
+            (0..row.len()).collect()
+        } else {
+            output_columns.clone()
+        };
+
+        let mut text = String::new();
+        let mut offset = None;
+        for (idx, &col) in columns.iter().enumerate() {
+            if idx > 0 {
+                text.push(char::from(options.delimiter));
+            }
+            let start = write_field(&mut text, row.get(col).unwrap_or_default(), options.delimiter);
+            if col == column && offset.is_none() {
+                offset = start;
+            }
+        }
+
+        records.push(Record {
+            line_number,
+            text,
+            field: Some(Field { value, offset }),
+            byte_offset: None,
+        });
+    }
+    Ok(records)
+}
+
+/// Turn a column name or 1-based number into a field index
+fn resolve_column(column: &str, header: Option<&csv::StringRecord>) -> Result<usize> {
+    if let Some(idx) = header.and_then(|header| header.iter().position(|name| name == column)) {
+        return Ok(idx);
+    }
+    match column.parse::<usize>() {
+        Ok(number) if number > 0 => Ok(number - 1),
+        _ => Err(PergError::InvalidPattern(format!("unknown CSV column: {}", column))),
+    }
+}
+
+/// Append a field, quoting it when needed; returns where the value starts verbatim, if it does
+pub(crate) fn write_field(output: &mut String, value: &str, delimiter: u8) -> Option<usize> {
+    let needs_quotes = value.contains(|c| c == char::from(delimiter) || matches!(c, '"' | '\n' | '\r'));
+    if !needs_quotes {
+        output.push_str(value);
+        return Some(output.len() - value.len());
+    }
+
+    output.push('"');
+    let start = output.len();
+    output.push_str(&value.replace('"', "\"\""));
+    output.push('"');
+    // Escaped quotes shift the value, so it no longer appears verbatim
+    (!value.contains('"')).then_some(start)
+}
+
+fn parse_error(err: csv::Error) -> PergError {
+    PergError::Parse(err.to_string())
+}
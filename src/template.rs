@@ -0,0 +1,235 @@
+//! Output line templates for `--format`.
+//!
+//! A template is literal text with `{placeholder}` fields and backslash escapes,
+//! e.g. `{path}:{line}:{col}: {match}`. The default output layouts are templates
+//! too, so every output line goes through [`Template::render`].
+
+use crate::color::{ColorScheme, Style};
+use crate::error::{PergError, Result};
+use crate::hyperlink::link;
+use regex::{Captures, Regex};
+
+/// A field filled in for each output line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Placeholder {
+    /// `{path}`: the file path, empty for stdin
+    Path,
+    /// `{line}`: the 1-based line number
+    Line,
+    /// `{col}`: the 1-based byte column of the match
+    Column,
+    /// `{offset}`: the byte offset of the match (or of the line without one) in the input
+    Offset,
+    /// `{text}`: the whole line, with matches highlighted
+    Text,
+    /// `{match}`: the text of the match
+    Match,
+    /// `{id}`: the labels of the patterns that matched
+    Id,
+    /// `{sep}`: the field separator, `:` on matching lines and `-` on context lines by default
+    Separator,
+    /// `{N}` or `{NAME}`: the text of a capture group of the match
+    Group(String),
+}
+
+impl Placeholder {
+    fn from_name(name: &str) -> Option<Self> {
+        let placeholder = match name {
+            "path" => Placeholder::Path,
+            "line" => Placeholder::Line,
+            "col" => Placeholder::Column,
+            "offset" => Placeholder::Offset,
+            "text" => Placeholder::Text,
+            "match" => Placeholder::Match,
+            "id" => Placeholder::Id,
+            "sep" => Placeholder::Separator,
+            name if is_group_name(name) => Placeholder::Group(name.to_string()),
+            _ => return None,
+        };
+        Some(placeholder)
+    }
+}
+
+/// Capture groups are referred to by number or by a name as the regex crate allows it
+fn is_group_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_digit() => name.chars().all(|c| c.is_ascii_digit()),
+        Some(first) if first.is_alphabetic() || first == '_' => chars.all(|c| c.is_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Literal(String),
+    Field(Placeholder),
+}
+
+/// Parsed output line template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+/// Values substituted into a template for one output line; missing values render empty
+#[derive(Debug, Default)]
+pub struct Fields<'a> {
+    pub path: Option<&'a str>,
+    pub line: usize,
+    pub column: Option<usize>,
+    pub offset: Option<usize>,
+    pub text: &'a str,
+    pub matched: Option<&'a str>,
+    pub id: Option<&'a str>,
+    pub separator: &'a str,
+    pub captures: Option<Captures<'a>>,
+    /// Styles for the path, line and column numbers and separators, when coloring
+    pub colors: Option<&'a ColorScheme>,
+    /// URL that the path and line number link to
+    pub link: Option<&'a str>,
+}
+
+impl Template {
+    /// Parse a template; `\t`, `\n`, `\r`, `\0`, `\\`, `\{` and `\}` are escapes
+    pub fn parse(spec: &str) -> Result<Self> {
+        let invalid = |message: String| PergError::InvalidTemplate(message);
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut chars = spec.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => literal.push(match chars.next() {
+                    Some('t') => '\t',
+                    Some('n') => '\n',
+                    Some('r') => '\r',
+                    Some('0') => '\0',
+                    Some(c @ ('\\' | '{' | '}')) => c,
+                    Some(c) => return Err(invalid(format!("unknown escape \\{}", c))),
+                    None => return Err(invalid("trailing backslash".to_string())),
+                }),
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(invalid(format!("unclosed placeholder {{{}", name))),
+                        }
+                    }
+                    let placeholder = Placeholder::from_name(&name)
+                        .ok_or_else(|| invalid(format!("unknown placeholder {{{}}}", name)))?;
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(Piece::Field(placeholder));
+                }
+                '}' => return Err(invalid("unmatched }".to_string())),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+        Ok(Self { pieces })
+    }
+
+    /// Whether the template contains a placeholder
+    pub fn uses(&self, placeholder: &Placeholder) -> bool {
+        self.pieces.iter().any(|piece| piece == &Piece::Field(placeholder.clone()))
+    }
+
+    /// Check that every capture group placeholder names a group of `regex`; without a
+    /// regex there are no groups to refer to
+    pub fn check_groups(&self, regex: Option<&Regex>) -> Result<()> {
+        for piece in &self.pieces {
+            let Piece::Field(Placeholder::Group(group)) = piece else {
+                continue;
+            };
+            let known = regex.is_some_and(|regex| match group.parse::<usize>() {
+                Ok(number) => number < regex.captures_len(),
+                Err(_) => regex.capture_names().flatten().any(|name| name == group),
+            });
+            if !known {
+                return Err(PergError::InvalidTemplate(format!("unknown placeholder {{{}}}", group)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Fill in the template for one output line
+    pub fn render(&self, fields: &Fields) -> String {
+        let paint = |text: &str, style: fn(&ColorScheme) -> &Style| match fields.colors {
+            Some(colors) => style(colors).paint(text),
+            None => text.to_string(),
+        };
+        let linked = |text: String| match fields.link {
+            Some(url) => link(url, &text),
+            None => text,
+        };
+        let number = |number: Option<usize>| number.map(|number| number.to_string()).unwrap_or_default();
+
+        let mut output = String::new();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Literal(text) => output.push_str(text),
+                Piece::Field(Placeholder::Path) => {
+                    output.push_str(&linked(paint(fields.path.unwrap_or_default(), |colors| &colors.path)))
+                }
+                Piece::Field(Placeholder::Line) => {
+                    output.push_str(&linked(paint(&fields.line.to_string(), |colors| &colors.line)))
+                }
+                Piece::Field(Placeholder::Column) => {
+                    output.push_str(&paint(&number(fields.column), |colors| &colors.column))
+                }
+                Piece::Field(Placeholder::Offset) => output.push_str(&number(fields.offset)),
+                Piece::Field(Placeholder::Text) => output.push_str(fields.text),
+                Piece::Field(Placeholder::Match) => output.push_str(fields.matched.unwrap_or_default()),
+                Piece::Field(Placeholder::Id) => output.push_str(fields.id.unwrap_or_default()),
+                Piece::Field(Placeholder::Separator) => {
+                    output.push_str(&paint(fields.separator, |colors| &colors.separator))
+                }
+                Piece::Field(Placeholder::Group(group)) => {
+                    let text = fields.captures.as_ref().and_then(|captures| match group.parse::<usize>() {
+                        Ok(number) => captures.get(number),
+                        Err(_) => captures.name(group),
+                    });
+                    output.push_str(text.map_or("", |m| m.as_str()));
+                }
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_template() {
+        let template = Template::parse(r"{path}{sep}{line}:{col}\t{match} [{user}|{2}] \{x\}").unwrap();
+        let regex = Regex::new(r"user=(?P<user>\w+)(?: id=(\d+))?").unwrap();
+        let fields = Fields {
+            path: Some("a.log"),
+            line: 3,
+            column: Some(5),
+            matched: Some("user=bob"),
+            separator: ":",
+            captures: regex.captures("x: user=bob"),
+            ..Fields::default()
+        };
+        assert_eq!(template.render(&fields), "a.log:3:5\tuser=bob [bob|] {x}");
+        assert!(template.check_groups(Some(&regex)).is_ok());
+        assert!(template.check_groups(Some(&Regex::new(r"(?P<user>\w+)").unwrap())).is_err());
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        for spec in ["{nope-x}", "{line", "line}", r"\q", "{}"] {
+            assert!(Template::parse(spec).is_err(), "{}", spec);
+        }
+        assert!(Template::parse("{text}").unwrap().uses(&Placeholder::Text));
+    }
+}
@@ -0,0 +1,274 @@
+//! Timestamp parsing and time-range filtering for log files.
+//!
+//! Recognized timestamp formats are RFC 3339 / ISO 8601 (`2026-10-01T10:00:00Z`,
+//! `2026-10-01 10:00`), syslog (`Oct  1 10:00:00`), Apache/nginx common log format
+//! (`[01/Oct/2026:10:00:00 +0000]`) and epoch seconds at the start of a line.
+//! Timestamps without a UTC offset are treated as UTC.
+
+use crate::error::{PergError, Result};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use regex::Regex;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+
+/// Stop bisecting once the search window is smaller than this many bytes
+const SEEK_GRANULARITY: u64 = 64 * 1024;
+
+/// Lines to read past a probe offset while looking for a timestamp
+const MAX_PROBE_LINES: usize = 256;
+
+/// Only the first bytes of a line are tried against a custom `--time-format`
+const CUSTOM_FORMAT_SCAN_BYTES: usize = 128;
+
+/// Inclusive time window applied to log lines via `--since` and `--until`
+#[derive(Debug, Clone)]
+pub struct TimeFilter {
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    parser: TimestampParser,
+}
+
+impl TimeFilter {
+    /// Build a filter from `--since`/`--until` values and an optional strftime-style format
+    pub fn new(since: Option<&str>, until: Option<&str>, time_format: Option<&str>) -> Result<Self> {
+        let parser = TimestampParser::new(time_format.map(str::to_string));
+        let since = since.map(|value| parser.parse_bound(value)).transpose()?;
+        let until = until.map(|value| parser.parse_bound(value)).transpose()?;
+        Ok(Self { since, until, parser })
+    }
+
+    /// Timestamp found in a line, if any
+    pub fn timestamp(&self, line: &str) -> Option<DateTime<Utc>> {
+        self.parser.parse_line(line)
+    }
+
+    /// Whether a timestamp falls inside the window
+    pub fn contains(&self, timestamp: DateTime<Utc>) -> bool {
+        self.since.is_none_or(|since| timestamp >= since) && self.until.is_none_or(|until| timestamp <= until)
+    }
+
+    /// Position a chronologically sorted file at a line start before which every
+    /// timestamped line is older than `--since`, found by binary search over byte offsets.
+    ///
+    /// Returns the number of lines skipped when `count_lines` is set, and 0 otherwise.
+    pub fn seek_to_window(&self, file: &mut File, count_lines: bool) -> io::Result<usize> {
+        let Some(since) = self.since else {
+            return Ok(0);
+        };
+
+        let (mut lo, mut hi) = (0, file.metadata()?.len());
+        while lo + SEEK_GRANULARITY < hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.next_timestamp_after(file, mid)? {
+                Some((offset, timestamp)) if timestamp < since => lo = offset,
+                _ => hi = mid,
+            }
+        }
+
+        let mut skipped = 0;
+        if count_lines && lo > 0 {
+            file.seek(SeekFrom::Start(0))?;
+            let mut prefix = BufReader::new(file.by_ref().take(lo));
+            loop {
+                let chunk = prefix.fill_buf()?;
+                if chunk.is_empty() {
+                    break;
+                }
+                skipped += chunk.iter().filter(|&&byte| byte == b'\n').count();
+                let consumed = chunk.len();
+                prefix.consume(consumed);
+            }
+        }
+
+        file.seek(SeekFrom::Start(lo))?;
+        Ok(skipped)
+    }
+
+    /// Find the first timestamped line that starts after `offset`, with its byte offset
+    fn next_timestamp_after(&self, file: &mut File, offset: u64) -> io::Result<Option<(u64, DateTime<Utc>)>> {
+        file.seek(SeekFrom::Start(offset))?;
+        let mut reader = BufReader::new(file.by_ref());
+        let mut line = Vec::new();
+
+        // The probe usually lands mid-line, so skip ahead to the next line start
+        let mut position = offset + reader.read_until(b'\n', &mut line)? as u64;
+        for _ in 0..MAX_PROBE_LINES {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 {
+                return Ok(None);
+            }
+            if let Some(timestamp) = self.timestamp(&String::from_utf8_lossy(&line)) {
+                return Ok(Some((position, timestamp)));
+            }
+            position += read as u64;
+        }
+        Ok(None)
+    }
+}
+
+/// Recognizes the supported timestamp formats
+#[derive(Debug, Clone)]
+struct TimestampParser {
+    format: Option<String>,
+    iso: Regex,
+    clf: Regex,
+    syslog: Regex,
+    epoch: Regex,
+}
+
+impl TimestampParser {
+    fn new(format: Option<String>) -> Self {
+        Self {
+            format,
+            iso: Regex::new(
+                r"\b(\d{4})-(\d{2})-(\d{2})[T ](\d{2}):(\d{2})(?::(\d{2})(?:[.,](\d{1,9}))?)?(Z|[+-]\d{2}:?\d{2})?",
+            )
+            .expect("valid ISO timestamp regex"),
+            clf: Regex::new(r"\[(\d{2}/[A-Za-z]{3}/\d{4}:\d{2}:\d{2}:\d{2} [+-]\d{4})\]")
+                .expect("valid common log format regex"),
+            syslog: Regex::new(r"^(?:<\d+>)?([A-Z][a-z]{2} +\d{1,2} \d{2}:\d{2}:\d{2})\b")
+                .expect("valid syslog timestamp regex"),
+            epoch: Regex::new(r"^\[?(\d{9,10})(?:\.(\d{1,9}))?\b").expect("valid epoch timestamp regex"),
+        }
+    }
+
+    /// Parse the first timestamp in a line
+    fn parse_line(&self, line: &str) -> Option<DateTime<Utc>> {
+        if let Some(format) = &self.format {
+            return parse_custom(line, format);
+        }
+        self.parse_iso(line)
+            .or_else(|| self.parse_clf(line))
+            .or_else(|| self.parse_syslog(line))
+            .or_else(|| self.parse_epoch(line))
+    }
+
+    /// Parse a `--since`/`--until` value
+    fn parse_bound(&self, value: &str) -> Result<DateTime<Utc>> {
+        let value = value.trim();
+        let parsed = if let Some(seconds) = value.strip_prefix('@') {
+            self.parse_epoch(seconds).filter(|_| seconds.chars().all(|c| c.is_ascii_digit() || c == '.'))
+        } else if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+            date.and_hms_opt(0, 0, 0).map(|midnight| midnight.and_utc())
+        } else {
+            self.parse_line(value)
+                .or_else(|| self.parse_iso(value))
+                .or_else(|| self.parse_epoch(value).filter(|_| value.chars().all(|c| c.is_ascii_digit() || c == '.')))
+        };
+        parsed.ok_or_else(|| PergError::InvalidTime(value.to_string()))
+    }
+
+    fn parse_iso(&self, line: &str) -> Option<DateTime<Utc>> {
+        let caps = self.iso.captures(line)?;
+        let number = |idx: usize| caps.get(idx).map_or(Some(0), |m| m.as_str().parse::<u32>().ok());
+        let date = NaiveDate::from_ymd_opt(caps[1].parse().ok()?, number(2)?, number(3)?)?;
+        let nanos = caps.get(7).map_or(Some(0), |m| {
+            // Scale fractional seconds of any precision to nanoseconds
+            format!("{:0<9}", m.as_str()).parse::<u32>().ok()
+        })?;
+        let naive = date.and_hms_nano_opt(number(4)?, number(5)?, number(6)?, nanos)?;
+
+        match caps.get(8).map(|m| m.as_str()) {
+            None | Some("Z") => Some(naive.and_utc()),
+            Some(offset) => {
+                let digits = offset[1..].replace(':', "");
+                let hours: i32 = digits[..2].parse().ok()?;
+                let minutes: i32 = digits[2..].parse().ok()?;
+                let seconds = (hours * 3600 + minutes * 60) * if offset.starts_with('-') { -1 } else { 1 };
+                let offset = FixedOffset::east_opt(seconds)?;
+                Some(offset.from_local_datetime(&naive).single()?.with_timezone(&Utc))
+            }
+        }
+    }
+
+    fn parse_clf(&self, line: &str) -> Option<DateTime<Utc>> {
+        let caps = self.clf.captures(line)?;
+        DateTime::parse_from_str(&caps[1], "%d/%b/%Y:%H:%M:%S %z")
+            .ok()
+            .map(|timestamp| timestamp.with_timezone(&Utc))
+    }
+
+    fn parse_syslog(&self, line: &str) -> Option<DateTime<Utc>> {
+        let caps = self.syslog.captures(line)?;
+        // Syslog timestamps carry no year: assume the current one, unless that lands in the future
+        let now = Utc::now();
+        let parse = |year: i32| {
+            NaiveDateTime::parse_from_str(&format!("{} {}", year, &caps[1]), "%Y %b %e %H:%M:%S")
+                .ok()
+                .map(|naive| naive.and_utc())
+        };
+        let timestamp = parse(now.year())?;
+        if timestamp > now + chrono::Duration::days(1) {
+            parse(now.year() - 1)
+        } else {
+            Some(timestamp)
+        }
+    }
+
+    fn parse_epoch(&self, line: &str) -> Option<DateTime<Utc>> {
+        let caps = self.epoch.captures(line)?;
+        let nanos = caps.get(2).map_or(Some(0), |m| format!("{:0<9}", m.as_str()).parse::<u32>().ok())?;
+        DateTime::from_timestamp(caps[1].parse().ok()?, nanos)
+    }
+}
+
+/// Parse a timestamp in a user-supplied strftime-style format, starting at the
+/// beginning of the line or of any word in its first bytes
+fn parse_custom(line: &str, format: &str) -> Option<DateTime<Utc>> {
+    let starts = std::iter::once(0).chain(
+        line.char_indices()
+            .take_while(|(idx, _)| *idx < CUSTOM_FORMAT_SCAN_BYTES)
+            .filter(|(_, c)| matches!(c, ' ' | '\t' | '[' | '"' | '(' | '='))
+            .map(|(idx, c)| idx + c.len_utf8()),
+    );
+
+    for start in starts {
+        let text = &line[start..];
+        if let Ok((timestamp, _)) = DateTime::parse_and_remainder(text, format) {
+            return Some(timestamp.with_timezone(&Utc));
+        }
+        if let Ok((naive, _)) = NaiveDateTime::parse_and_remainder(text, format) {
+            return Some(naive.and_utc());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_parses_supported_formats() {
+        let filter = TimeFilter::new(None, None, None).unwrap();
+
+        assert_eq!(
+            filter.timestamp("2026-10-01T10:00:00.5+02:00 ERROR x"),
+            Some(utc("2026-10-01T08:00:00.5Z"))
+        );
+        assert_eq!(filter.timestamp("2026-10-01 10:00 INFO"), Some(utc("2026-10-01T10:00:00Z")));
+        assert_eq!(
+            filter.timestamp(r#"10.0.0.1 - - [01/Oct/2026:10:00:00 +0000] "GET / HTTP/1.1" 200"#),
+            Some(utc("2026-10-01T10:00:00Z"))
+        );
+        assert_eq!(filter.timestamp("1790848800 started"), Some(utc("2026-10-01T10:00:00Z")));
+        assert!(filter.timestamp("Oct  1 10:00:00 host sshd[1]: accepted").is_some());
+        assert_eq!(filter.timestamp("\tat Foo.bar(Foo.java:1)"), None);
+    }
+
+    #[test]
+    fn test_custom_format_and_bounds() {
+        let filter = TimeFilter::new(Some("2026-10-01"), Some("2026-10-01T11:00"), Some("%d.%m.%Y %H:%M")).unwrap();
+
+        let timestamp = filter.timestamp("[worker] 01.10.2026 10:30 done").unwrap();
+        assert_eq!(timestamp, utc("2026-10-01T10:30:00Z"));
+        assert!(filter.contains(timestamp));
+        assert!(!filter.contains(utc("2026-10-01T11:00:01Z")));
+        assert!(TimeFilter::new(Some("yesterday"), None, None).is_err());
+    }
+}
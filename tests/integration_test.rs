@@ -168,6 +168,26 @@ fn test_regex_search() {
        .stdout(predicate::str::diff("goodbye").not().normalize());
 }
 
+#[test]
+fn test_only_matching_prints_bare_matches() {
+    let temp_dir = TempDir::new().unwrap();
+    let a = temp_dir.path().join("a.txt");
+    let b = temp_dir.path().join("b.txt");
+    std::fs::write(&a, "x foo y foo\n").unwrap();
+    std::fs::write(&b, "bar\nfoo\n").unwrap();
+
+    // Each match is printed on its own, without path or line number prefixes
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-o")
+       .arg("-n")
+       .arg("foo")
+       .arg(&a)
+       .arg(&b)
+       .assert()
+       .success()
+       .stdout("foo\nfoo\nfoo\n");
+}
+
 #[test]
 fn test_no_matches() {
     let temp_dir = TempDir::new().unwrap();
@@ -206,3 +226,1096 @@ fn test_invalid_regex() {
        .failure()
        .stderr(predicate::str::contains("Regex error"));
 }
+
+#[test]
+fn test_multiline_match_spans_lines() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("lib.rs");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(file, "struct Old {{").unwrap();
+    writeln!(file, "    // deprecated").unwrap();
+    writeln!(file, "}}").unwrap();
+    writeln!(file, "struct New {{").unwrap();
+    writeln!(file, "}}").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-U")
+       .arg("-n")
+       .arg(r"struct \w+ \{[^}]*deprecated")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("1:struct Old {\n2:    // deprecated\n");
+}
+
+#[test]
+fn test_multiline_empty_input() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("empty.txt");
+    File::create(&file_path).unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-U")
+       .arg("^")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout(predicate::str::is_empty());
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-U")
+       .arg("-c")
+       .arg("x*")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout(format!("{}:0\n", file_path.display()));
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-U")
+       .arg("x*")
+       .write_stdin("")
+       .assert()
+       .success()
+       .stdout(predicate::str::is_empty());
+}
+
+#[test]
+fn test_multiline_dotall() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("test.txt");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(file, "begin").unwrap();
+    writeln!(file, "middle").unwrap();
+    writeln!(file, "end").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-U")
+       .arg("begin.*end")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout(predicate::str::is_empty());
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-U")
+       .arg("--multiline-dotall")
+       .arg("begin.*end")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("begin\nmiddle\nend\n");
+}
+
+#[test]
+fn test_null_data_records() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("environ");
+    std::fs::write(&file_path, "HOME=/root\0PATH=/usr/bin\nmore\0SHELL=/bin/sh\0").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-z")
+       .arg("PATH|SHELL")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("PATH=/usr/bin\nmore\0SHELL=/bin/sh\0");
+}
+
+#[test]
+fn test_null_after_filenames() {
+    let temp_dir = TempDir::new().unwrap();
+    let file1_path = temp_dir.path().join("file1.txt");
+    let file2_path = temp_dir.path().join("file2.txt");
+    std::fs::write(&file1_path, "hello world\n").unwrap();
+    std::fs::write(&file2_path, "no matches\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-l")
+       .arg("-Z")
+       .arg("world")
+       .arg(&file1_path)
+       .arg(&file2_path)
+       .assert()
+       .success()
+       .stdout(format!("{}\0", file1_path.display()));
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-Z")
+       .arg("world")
+       .arg(&file1_path)
+       .arg(&file2_path)
+       .assert()
+       .success()
+       .stdout(format!("{}\0hello world\n", file1_path.display()));
+}
+
+#[test]
+fn test_paragraph_records() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("dump.txt");
+    std::fs::write(&file_path, "alpha one\nalpha two\n\n\nbeta one\nERROR here\n\ngamma\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--paragraph")
+       .arg("-n")
+       .arg("ERROR")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("5:beta one\nERROR here\n");
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--paragraph")
+       .arg("-c")
+       .arg("-v")
+       .arg("ERROR")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout(predicate::str::ends_with(":2\n"));
+}
+
+#[test]
+fn test_custom_record_separator() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("docs.yaml");
+    std::fs::write(&file_path, "name: a\n---\nname: b\nimage: latest\n---\nname: c\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--record-separator")
+       .arg("^---$")
+       .arg("-n")
+       .arg("-m")
+       .arg("1")
+       .arg("^name")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("1:name: a\n");
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--record-separator")
+       .arg("^---$")
+       .arg("-n")
+       .arg("latest")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("3:name: b\nimage: latest\n");
+}
+
+#[test]
+fn test_record_start_groups_stack_traces() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("app.log");
+    std::fs::write(
+        &file_path,
+        "2026-10-01 10:00:00 INFO start\n\
+         2026-10-01 10:00:01 ERROR boom\n\
+         java.lang.RuntimeException: x\n\
+         Caused by: java.io.IOException: disk\n\
+         2026-10-01 10:00:02 INFO ok\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--record-start")
+       .arg(r"^\d{4}-\d{2}-\d{2}")
+       .arg("-n")
+       .arg("Caused by")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout(
+           "2:2026-10-01 10:00:01 ERROR boom\n\
+            java.lang.RuntimeException: x\n\
+            Caused by: java.io.IOException: disk\n",
+       );
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--record-start")
+       .arg(r"^\d{4}-\d{2}-\d{2}")
+       .arg("-c")
+       .arg("INFO")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout(predicate::str::ends_with(":2\n"));
+}
+
+#[test]
+fn test_time_range_filtering() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("app.log");
+    let mut file = File::create(&file_path).unwrap();
+    // Large enough for the binary search to skip ahead before reading
+    for minute in 0..(24 * 60) {
+        writeln!(file, "2026-10-01T{:02}:{:02}:00Z INFO tick {}", minute / 60, minute % 60, "x".repeat(64)).unwrap();
+        if minute == 10 * 60 + 30 {
+            writeln!(file, "2026-10-01T10:30:30Z ERROR disk full").unwrap();
+            writeln!(file, "    caused by: ERROR in continuation").unwrap();
+        }
+    }
+    writeln!(file, "2026-10-02T10:30:00Z ERROR next day").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--since")
+       .arg("2026-10-01T10:00")
+       .arg("--until")
+       .arg("2026-10-01T11:00")
+       .arg("-n")
+       .arg("ERROR")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("632:2026-10-01T10:30:30Z ERROR disk full\n633:    caused by: ERROR in continuation\n");
+
+    // Line numbers printed without -n are counted from the start of the file too
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--since")
+       .arg("2026-10-01T10:00")
+       .arg("--until")
+       .arg("2026-10-01T11:00")
+       .arg("--format")
+       .arg("{line}")
+       .arg("ERROR")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("632\n633\n");
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--since")
+       .arg("2026-10-01T10:00")
+       .arg("--until")
+       .arg("2026-10-01T11:00")
+       .arg("--json")
+       .arg("disk full")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout(predicate::str::contains("\"line_number\":632,"));
+}
+
+#[test]
+fn test_invalid_time_bound() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("app.log");
+    File::create(&file_path).unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--since")
+       .arg("last tuesday")
+       .arg("ERROR")
+       .arg(&file_path)
+       .assert()
+       .code(2)
+       .stderr(predicate::str::contains("Invalid time"));
+}
+
+#[test]
+fn test_csv_column_search() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("people.csv");
+    std::fs::write(
+        &file_path,
+        "id,name,note\n1,alice,\"likes\nbob\"\n2,bob,plain\n3,\"carol \"\"cc\"\"\",bob\n",
+    )
+    .unwrap();
+
+    // Quoted fields with embedded newlines stay within their record
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--csv-column")
+       .arg("name")
+       .arg("-n")
+       .arg("bob")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("4:2,bob,plain\n");
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--csv-column")
+       .arg("3")
+       .arg("-v")
+       .arg("-c")
+       .arg("bob")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout(predicate::str::ends_with(":1\n"));
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--csv-column")
+       .arg("name")
+       .arg("--csv-output")
+       .arg("name,id")
+       .arg("carol")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("\"carol \"\"cc\"\"\",3\n");
+}
+
+#[test]
+fn test_tsv_unknown_column() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("data.tsv");
+    std::fs::write(&file_path, "host\tstatus\nweb1\tdown\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--csv-column")
+       .arg("status")
+       .arg("--tsv")
+       .arg("down")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("web1\tdown\n");
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--csv-column")
+       .arg("latency")
+       .arg("--tsv")
+       .arg("down")
+       .arg(&file_path)
+       .assert()
+       .failure()
+       .stderr(predicate::str::contains("unknown CSV column: latency"));
+}
+
+#[test]
+fn test_json_path_search() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("requests.jsonl");
+    std::fs::write(
+        &file_path,
+        "{\"request\":{\"headers\":{\"user_agent\":\"curl/8.0\"}},\"note\":\"Mozilla\"}\n\
+         {\"request\":{\"headers\":{\"user_agent\":\"Mozilla/5.0\"}}}\n\
+         {\"items\":[{\"name\":\"a\"},{\"name\":\"Mozilla\"}]}\n",
+    )
+    .unwrap();
+
+    // Only values at the path count, not the same text elsewhere in the record
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--json-path")
+       .arg(".request.headers.user_agent")
+       .arg("-n")
+       .arg("Mozilla")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("2:{\"request\":{\"headers\":{\"user_agent\":\"Mozilla/5.0\"}}}\n");
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--json-path")
+       .arg(".items[*].name")
+       .arg("--json-pretty")
+       .arg("-n")
+       .arg("^Mozilla$")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout(predicate::str::starts_with("3:{\n  \"items\": [\n"));
+}
+
+#[test]
+fn test_json_documents_and_parse_errors() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("docs.json");
+    std::fs::write(&file_path, "{\n  \"level\": \"info\"\n}\n{\n  \"level\": \"error\"\n}\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--json-path")
+       .arg("level")
+       .arg("-n")
+       .arg("error")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("4:{\n  \"level\": \"error\"\n}\n");
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--json-path")
+       .arg(".level")
+       .arg("error")
+       .write_stdin("{\n  \"level\": \"error\"\n}\n{oops\n")
+       .assert()
+       .success()
+       .stdout("{\n  \"level\": \"error\"\n}\n")
+       .stderr(predicate::str::contains("Parse error: line 4: invalid JSON"));
+
+    // A malformed document over several lines is one error at the line where it breaks,
+    // and none of its pieces become records
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--json-path")
+       .arg(".")
+       .arg("-n")
+       .arg("a|1|2")
+       .write_stdin("{\n \"a\": \"hello\",\n \"b\": [1,\n 2,,\n ]\n}\n")
+       .assert()
+       .failure()
+       .stdout("")
+       .stderr("perg: (standard input): Parse error: line 4: invalid JSON: expected value\n");
+}
+
+#[test]
+fn test_json_lines_malformed_record() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("events.ndjson");
+    std::fs::write(&file_path, "{\"a\":\"curl 1\"}\n{\"a\":\"wget\"}\nnot json\n{\"a\":\"curl 2\"}\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--json-path")
+       .arg(".a")
+       .arg("-n")
+       .arg("curl")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("1:{\"a\":\"curl 1\"}\n4:{\"a\":\"curl 2\"}\n")
+       .stderr(predicate::str::contains("line 3: invalid JSON"));
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--json-path")
+       .arg(".a")
+       .arg("-s")
+       .arg("curl")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stderr("");
+}
+
+#[test]
+fn test_key_path_yaml_and_toml() {
+    let temp_dir = TempDir::new().unwrap();
+    std::fs::write(
+        temp_dir.path().join("deploy.yaml"),
+        "spec:\n  containers:\n    - image: nginx:1.25\n---\nspec:\n  containers:\n    - image: redis:latest\n",
+    )
+    .unwrap();
+    std::fs::write(temp_dir.path().join("app.toml"), "[server]\nimage = \"api:latest\"\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--key-path")
+       .arg("spec.containers[*].image")
+       .arg("-n")
+       .arg(":latest")
+       .arg(temp_dir.path().join("deploy.yaml"))
+       .assert()
+       .success()
+       .stdout("7:    - image: redis:latest\n");
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--key-path")
+       .arg(".server.image")
+       .arg("-n")
+       .arg(":latest")
+       .arg(temp_dir.path().join("app.toml"))
+       .assert()
+       .success()
+       .stdout("2:image = \"api:latest\"\n");
+}
+
+#[test]
+fn test_key_path_falls_back_to_text_search() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("broken.toml");
+    std::fs::write(&file_path, "image = \"api:latest\"\n[unclosed\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--key-path")
+       .arg(".image")
+       .arg(":latest")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("image = \"api:latest\"\n")
+       .stderr(predicate::str::contains("Parse error: invalid TOML at line 2"));
+
+    // The fallback warning is a message like any other, silenced by -s
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--key-path")
+       .arg(".image")
+       .arg("-s")
+       .arg(":latest")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("image = \"api:latest\"\n")
+       .stderr("");
+}
+
+#[test]
+fn test_number_range_after_key() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("access.log");
+    std::fs::write(&file_path, "GET /a latency_ms=612 size=80\nGET /b latency_ms=80 size=1,204\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--number-range")
+       .arg(">500")
+       .arg("--number-key")
+       .arg("latency_ms=")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("GET /a latency_ms=612 size=80\n");
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-o")
+       .arg("--number-range")
+       .arg("[1000,2000)")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("1,204\n");
+}
+
+#[test]
+fn test_number_range_rejects_invalid_range() {
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--number-range")
+       .arg("fast")
+       .write_stdin("1\n")
+       .assert()
+       .code(2)
+       .stderr(predicate::str::contains("invalid number range: fast"));
+}
+
+#[test]
+fn test_cidr_matches_whole_addresses() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("access.log");
+    std::fs::write(
+        &file_path,
+        "10.1.2.3 GET /\n110.0.0.1 GET /\n[2001:db8::7]:443 GET /\nperg 1.10.0.0.1\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-o")
+       .arg("--cidr")
+       .arg("10.0.0.0/8,2001:db8::/32")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("10.1.2.3\n2001:db8::7\n");
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-v")
+       .arg("-c")
+       .arg("--cidr")
+       .arg("10.0.0.0/8")
+       .write_stdin(std::fs::read_to_string(&file_path).unwrap())
+       .assert()
+       .success()
+       .stdout("3\n");
+}
+
+#[test]
+fn test_fuzzy_reports_edit_distance() {
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-o")
+       .arg("--fuzzy")
+       .arg("1")
+       .arg("receive")
+       .write_stdin("we recive mail\nreceive ok\nrcv\n")
+       .assert()
+       .success()
+       .stdout("1:recive\n0:receive\n");
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--json")
+       .arg("-n")
+       .arg("--fuzzy")
+       .arg("1")
+       .arg("receive")
+       .write_stdin("we recive mail\n")
+       .assert()
+       .success()
+       .stdout(concat!(
+           r#"{"type":"match","path":null,"line_number":1,"text":"we recive mail","#,
+           r#""matches":[{"text":"recive","start":3,"end":9,"distance":1}]}"#,
+           "\n"
+       ));
+}
+
+#[test]
+fn test_ignore_diacritics_reports_original_text() {
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-i")
+       .arg("--ignore-diacritics")
+       .arg("-o")
+       .arg("strasse|cafe")
+       .write_stdin("Hauptstraße 5\nla cafe\u{301}\nnothing\n")
+       .assert()
+       .success()
+       .stdout("straße\ncafe\u{301}\n");
+
+    // Columns count bytes of the original line
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-i")
+       .arg("--ignore-diacritics")
+       .arg("-n")
+       .arg("--column")
+       .arg("strasse|cafe")
+       .write_stdin("Hauptstraße 5\nla cafe\u{301}\nnothing\n")
+       .assert()
+       .success()
+       .stdout("1:6:Hauptstraße 5\n2:4:la cafe\u{301}\n");
+}
+
+#[test]
+fn test_normalize_nfc_matches_decomposed_input() {
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--normalize")
+       .arg("nfc")
+       .arg("caf\u{e9}")
+       .write_stdin("cafe\u{301} au lait\ncafe noir\n")
+       .assert()
+       .success()
+       .stdout("cafe\u{301} au lait\n");
+}
+
+#[test]
+fn test_boolean_expression() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("notes.txt");
+    std::fs::write(&file_path, "foo bar\nfoo baz test\nfoo qux\nbar only\nfoo baz\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-n")
+       .arg("-e")
+       .arg("foo")
+       .arg("--and")
+       .arg("(")
+       .arg("-e")
+       .arg("bar")
+       .arg("--or")
+       .arg("-e")
+       .arg("baz")
+       .arg(")")
+       .arg("--not")
+       .arg("-e")
+       .arg("test")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("1:foo bar\n5:foo baz\n");
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-e")
+       .arg("foo")
+       .arg("--and")
+       .arg("(")
+       .arg("-e")
+       .arg("bar")
+       .arg(&file_path)
+       .assert()
+       .code(2)
+       .stderr(predicate::str::contains("invalid expression: unmatched '('"));
+}
+
+#[test]
+fn test_all_match_requires_every_pattern_in_file() {
+    let temp_dir = TempDir::new().unwrap();
+    std::fs::write(temp_dir.path().join("a.rs"), "unsafe impl Foo\nfn x()\nimpl Send for Foo\n").unwrap();
+    std::fs::write(temp_dir.path().join("b.rs"), "unsafe fn y()\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-l")
+       .arg("--all-match")
+       .arg("-e")
+       .arg("unsafe")
+       .arg("-e")
+       .arg("Send")
+       .arg(temp_dir.path().join("a.rs"))
+       .arg(temp_dir.path().join("b.rs"))
+       .assert()
+       .success()
+       .stdout(format!("{}\n", temp_dir.path().join("a.rs").display()));
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--all-match")
+       .arg("-e")
+       .arg("unsafe")
+       .arg("-e")
+       .arg("Send")
+       .write_stdin("unsafe impl Foo\nfn x()\nimpl Send for Foo\n")
+       .assert()
+       .success()
+       .stdout("unsafe impl Foo\nimpl Send for Foo\n");
+}
+
+#[test]
+fn test_near_and_not_near() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("locks.c");
+    std::fs::write(&file_path, "a lock()\nwork\nunlock()\nx\nlock()\ny\nz\nw\nlock()\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-n")
+       .arg("--near")
+       .arg("2")
+       .arg("-e")
+       .arg(r"\block\(")
+       .arg("-e")
+       .arg(r"unlock\(")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("1:a lock()\n2-work\n3:unlock()\n4-x\n5:lock()\n");
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-n")
+       .arg("--not-near")
+       .arg("2")
+       .arg("-e")
+       .arg(r"\block\(")
+       .arg("-e")
+       .arg(r"unlock\(")
+       .arg(&file_path)
+       .assert()
+       .success()
+       .stdout("9:lock()\n");
+}
+
+#[test]
+fn test_pattern_file_attribution_and_stats() {
+    let temp_dir = TempDir::new().unwrap();
+    let patterns_path = temp_dir.path().join("iocs.txt");
+    std::fs::write(&patterns_path, "evil.com\nssh-key\t^ssh-\n\nneverseen\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-n")
+       .arg("--pattern-id")
+       .arg("-f")
+       .arg(&patterns_path)
+       .write_stdin("GET evil.com\nssh-rsa AAA\nclean\n")
+       .assert()
+       .success()
+       .stdout("1:1:GET evil.com\n2:ssh-key:ssh-rsa AAA\n");
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-c")
+       .arg("--pattern-stats")
+       .arg("-f")
+       .arg(&patterns_path)
+       .write_stdin("GET evil.com\nssh-rsa AAA\nevil.com again\n")
+       .assert()
+       .success()
+       .stdout(concat!(
+           "3\n",
+           "PATTERN     LINES  REGEX\n",
+           "1               2  evil.com\n",
+           "ssh-key         1  ^ssh-\n",
+           "3               0  neverseen\n",
+       ));
+}
+
+#[test]
+fn test_frequency_table() {
+    let temp_dir = TempDir::new().unwrap();
+    std::fs::write(temp_dir.path().join("a.log"), "user=alice x\nuser=bob user=alice\n").unwrap();
+    std::fs::write(temp_dir.path().join("b.log"), "user=carol\nuser=alice\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--frequency")
+       .arg("--top")
+       .arg("2")
+       .arg("--group")
+       .arg("name")
+       .arg(r"user=(?P<name>\w+)")
+       .arg(temp_dir.path().join("a.log"))
+       .arg(temp_dir.path().join("b.log"))
+       .assert()
+       .success()
+       .stdout("      3 alice\n      1 bob\n");
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--unique")
+       .arg(r"user=\w+")
+       .write_stdin("user=bob user=alice\nuser=bob\n")
+       .assert()
+       .success()
+       .stdout("user=bob\nuser=alice\n");
+}
+
+#[test]
+fn test_extract_capture_groups() {
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--extract")
+       .arg("csv")
+       .arg(r"(?P<lvl>WARN|INFO) (?P<msg>[^|]*)(?:\|(\d+))?")
+       .write_stdin("WARN disk \"sda\" full, 91%\nnothing\nINFO ok|7\n")
+       .assert()
+       .success()
+       .stdout("lvl,msg,3\nWARN,\"disk \"\"sda\"\" full, 91%\",\nINFO,ok,7\n");
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-n")
+       .arg("--extract")
+       .arg("json")
+       .arg(r"user=(?P<name>\w+)")
+       .write_stdin("x\nuser=alice user=bob\n")
+       .assert()
+       .success()
+       .stdout("{\"line\":2,\"name\":\"alice\"}\n{\"line\":2,\"name\":\"bob\"}\n");
+}
+
+#[test]
+fn test_format_template() {
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-C")
+       .arg("1")
+       .arg("--format")
+       .arg(r"{line}{sep}{offset}\t{match}<{name}|{2}>")
+       .arg(r"user=(?P<name>\w+)(?: id=(\d+))?")
+       .write_stdin("user=alice id=1\nnothing\r\nx user=bob\n")
+       .assert()
+       .success()
+       .stdout("1:0\tuser=alice id=1<alice|1>\n2-16\t<|>\n3:27\tuser=bob<bob|>\n");
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--format")
+       .arg("{line}:{user}")
+       .arg(r"(?P<name>\w+)")
+       .write_stdin("alice\n")
+       .assert()
+       .code(2)
+       .stderr(predicate::str::contains("unknown placeholder {user}"));
+}
+
+#[test]
+fn test_heading_layout() {
+    let temp_dir = TempDir::new().unwrap();
+    let a = temp_dir.path().join("a.txt");
+    let b = temp_dir.path().join("b.txt");
+    let c = temp_dir.path().join("c.txt");
+    std::fs::write(&a, "a foo\nb\nc foo\n").unwrap();
+    std::fs::write(&b, "nothing\n").unwrap();
+    std::fs::write(&c, "foo x\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--heading")
+       .arg("-A")
+       .arg("1")
+       .arg("foo")
+       .arg(&a)
+       .arg(&b)
+       .arg(&c)
+       .assert()
+       .success()
+       .stdout(format!("{}\n1:a foo\n2-b\n3:c foo\n\n{}\n1:foo x\n", a.display(), c.display()));
+
+    // Piped output keeps the flat layout by default
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("foo")
+       .arg(&a)
+       .arg(&c)
+       .assert()
+       .success()
+       .stdout(format!("{0}:a foo\n{0}:c foo\n{1}:foo x\n", a.display(), c.display()));
+
+    // With -Z the heading path ends in NUL
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--heading")
+       .arg("-Z")
+       .arg("foo")
+       .arg(&a)
+       .arg(&c)
+       .assert()
+       .success()
+       .stdout(format!("{}\x001:a foo\n3:c foo\n\n{}\x001:foo x\n", a.display(), c.display()));
+
+    // Headings would break structured output, so they can't be combined with it
+    for flag in ["--json", "--frequency"] {
+        let mut cmd = Command::cargo_bin("perg").unwrap();
+        cmd.arg("--heading")
+           .arg(flag)
+           .arg("foo")
+           .arg(&a)
+           .assert()
+           .failure()
+           .stderr(predicate::str::contains("cannot be used with"));
+    }
+}
+
+#[test]
+fn test_color_styles() {
+    // --color=always colors even when piped, with GREP_COLORS and then --colors applied
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.env("GREP_COLORS", "ms=04;32:ln=38;5;208")
+       .arg("--color=always")
+       .arg("--colors")
+       .arg("separator:none")
+       .arg("-n")
+       .arg("foo")
+       .write_stdin("a foo\n")
+       .assert()
+       .success()
+       .stdout("\x1b[38;5;208m1\x1b[0m:a \x1b[4;32mfoo\x1b[0m\n");
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--colors")
+       .arg("match:fg:purple")
+       .arg("foo")
+       .write_stdin("foo\n")
+       .assert()
+       .code(2)
+       .stderr(predicate::str::contains("Invalid color spec"));
+}
+
+#[test]
+fn test_hyperlinks() {
+    let temp_dir = TempDir::new().unwrap();
+    std::fs::write(temp_dir.path().join("a.txt"), "x\na foo\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.current_dir(temp_dir.path())
+       .arg("--color=always")
+       .arg("--colors")
+       .arg("path:none")
+       .arg("--colors")
+       .arg("line:none")
+       .arg("--colors")
+       .arg("separator:none")
+       .arg("--colors")
+       .arg("match:none")
+       .arg("--hyperlink-format")
+       .arg("vscode")
+       .arg("-Hn")
+       .arg("foo")
+       .arg("a.txt")
+       .assert()
+       .success()
+       .stdout(predicate::str::contains(":2:3\x1b\\a.txt\x1b]8;;\x1b\\:"))
+       .stdout(predicate::str::starts_with("\x1b]8;;vscode://file/"));
+
+    // Without color there are no links
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.current_dir(temp_dir.path())
+       .arg("--hyperlink-format")
+       .arg("vscode")
+       .arg("-Hn")
+       .arg("foo")
+       .arg("a.txt")
+       .assert()
+       .success()
+       .stdout("a.txt:2:a foo\n");
+}
+
+#[test]
+fn test_passthru() {
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--passthru")
+       .arg("-n")
+       .arg("foo")
+       .write_stdin("a foo\nb\nc\nd foo\ne\n")
+       .assert()
+       .success()
+       .stdout("1:a foo\n2-b\n3-c\n4:d foo\n5-e\n");
+}
+
+#[test]
+fn test_max_columns() {
+    let input = format!("{}needle{}needle\nshort needle\n", "x".repeat(40), "y".repeat(40));
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--max-columns")
+       .arg("30")
+       .arg("-n")
+       .arg("needle")
+       .write_stdin(input.clone())
+       .assert()
+       .success()
+       .stdout("1:[omitted long line with 2 matches]\n2:short needle\n");
+
+    // Lines selected by -v have no matches to count
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--max-columns")
+       .arg("30")
+       .arg("-v")
+       .arg("short")
+       .write_stdin(input.clone())
+       .assert()
+       .success()
+       .stdout("[omitted long line]\n");
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--max-columns")
+       .arg("16")
+       .arg("--max-columns-preview")
+       .arg("needle")
+       .write_stdin(input)
+       .assert()
+       .success()
+       .stdout("…xxxxxneedleyyyyy…\nshort needle\n");
+}
+
+#[test]
+fn test_group_separators() {
+    let temp_dir = TempDir::new().unwrap();
+    let a = temp_dir.path().join("a.txt");
+    let b = temp_dir.path().join("b.txt");
+    let c = temp_dir.path().join("c.txt");
+    std::fs::write(&a, "foo\nx\ny\nz\nfoo\nfoo\n").unwrap();
+    std::fs::write(&b, "nothing\n").unwrap();
+    std::fs::write(&c, "foo\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-n")
+       .arg("-A")
+       .arg("1")
+       .arg("foo")
+       .arg(&a)
+       .assert()
+       .success()
+       .stdout("1:foo\n2-x\n--\n5:foo\n6:foo\n");
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-A")
+       .arg("1")
+       .arg("--group-separator")
+       .arg("~~")
+       .arg("--field-match-separator")
+       .arg(" | ")
+       .arg("--field-context-separator")
+       .arg(" ")
+       .arg("foo")
+       .arg(&a)
+       .arg(&b)
+       .arg(&c)
+       .assert()
+       .success()
+       .stdout(format!(
+           "{0} | foo\n{0} x\n~~\n{0} | foo\n{0} | foo\n~~\n{1} | foo\n",
+           a.display(),
+           c.display()
+       ));
+
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("-A")
+       .arg("1")
+       .arg("--no-group-separator")
+       .arg("foo")
+       .arg(&a)
+       .assert()
+       .success()
+       .stdout("foo\nx\nfoo\nfoo\n");
+
+    // JSON Lines output has no separators between groups
+    let mut cmd = Command::cargo_bin("perg").unwrap();
+    cmd.arg("--json")
+       .arg("-A")
+       .arg("1")
+       .arg("foo")
+       .arg(&a)
+       .assert()
+       .success()
+       .stdout(predicate::str::contains("--").not())
+       .stdout(predicate::function(|out: &str| out.lines().all(|line| line.starts_with('{'))));
+}